@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Live reload for `eighty serve`. Injected into every HTML page served in
+// development. Reloads the page after each successful rebuild, keeping the
+// scroll position, and shows rebuild errors in an overlay. While the last
+// rebuild has failed, the server passes its error in `data-build-error` so
+// that the overlay is shown on every page until the next successful rebuild.
+
+(function () {
+  "use strict";
+
+  const EVENTS_URL = "/__eighty/events";
+  const SCROLL_KEY = "eighty-live-reload-scroll:" + location.pathname;
+  const OVERLAY_ID = "eighty-live-reload-overlay";
+  const BUILD_ERROR = document.currentScript && document.currentScript.dataset.buildError;
+
+  function restoreScroll() {
+    const saved = sessionStorage.getItem(SCROLL_KEY);
+    if (saved === null) {
+      return;
+    }
+    sessionStorage.removeItem(SCROLL_KEY);
+
+    const [x, y] = saved.split(",").map(Number);
+    window.scrollTo(x, y);
+  }
+
+  function reload() {
+    sessionStorage.setItem(SCROLL_KEY, window.scrollX + "," + window.scrollY);
+    location.reload();
+  }
+
+  function showError(message) {
+    let overlay = document.getElementById(OVERLAY_ID);
+    if (!overlay) {
+      overlay = document.createElement("div");
+      overlay.id = OVERLAY_ID;
+      overlay.style.cssText =
+        "position:fixed;inset:0;z-index:2147483647;overflow:auto;padding:2em;" +
+        "background:rgba(0,0,0,0.85);color:#fff;font:14px/1.5 monospace;";
+
+      const close = document.createElement("button");
+      close.textContent = "×";
+      close.style.cssText =
+        "position:absolute;top:1em;right:1em;font-size:1.5em;background:none;" +
+        "border:none;color:#fff;cursor:pointer;";
+      close.addEventListener("click", () => overlay.remove());
+      overlay.appendChild(close);
+
+      const title = document.createElement("h2");
+      title.textContent = "Rebuild failed";
+      title.style.cssText = "color:#ff6b6b;margin-top:0;";
+      overlay.appendChild(title);
+
+      const pre = document.createElement("pre");
+      pre.style.whiteSpace = "pre-wrap";
+      overlay.appendChild(pre);
+
+      document.body.appendChild(overlay);
+    }
+    overlay.querySelector("pre").textContent = message;
+  }
+
+  function connect() {
+    const events = new EventSource(EVENTS_URL);
+    events.addEventListener("reload", reload);
+    events.addEventListener("build-error", (event) => showError(JSON.parse(event.data)));
+  }
+
+  window.addEventListener("load", restoreScroll);
+  if (BUILD_ERROR) {
+    window.addEventListener("load", () => showError(BUILD_ERROR));
+  }
+  connect();
+})();
@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Client-side search over the `search-index.json` generated for a site.
+//
+// Usage: include this script, then add an input with a
+// `data-eighty-search` attribute pointing to the index URL, and an element
+// with a `data-eighty-search-results` attribute to hold the results:
+//
+//   <input type="search" data-eighty-search="/search-index.json">
+//   <ul data-eighty-search-results></ul>
+//
+// The `EightySearch` global can also be used directly:
+//
+//   EightySearch.load("/search-index.json").then((search) => search.query("foo"));
+
+(function () {
+  "use strict";
+
+  const SUPPORTED_VERSION = 1;
+
+  function tokenize(text) {
+    return text
+      .toLowerCase()
+      .split(/[^\p{L}\p{N}]+/u)
+      .filter((token) => token.length > 0);
+  }
+
+  class Search {
+    constructor(index) {
+      if (index.version !== SUPPORTED_VERSION) {
+        throw new Error("Unsupported search index version " + index.version);
+      }
+
+      this.documents = index.documents.map((document) => ({
+        document,
+        titleTokens: tokenize(document.title),
+        headingTokens: document.headings.map((heading) => tokenize(heading.title)),
+        tokens: document.tokens,
+      }));
+    }
+
+    query(text) {
+      const terms = tokenize(text);
+      if (terms.length === 0) {
+        return [];
+      }
+
+      const results = [];
+      for (const entry of this.documents) {
+        let score = 0;
+        let heading = null;
+
+        const matched = terms.every((term) => {
+          const matches = (token) => token.startsWith(term);
+          let termScore = 0;
+
+          if (entry.titleTokens.some(matches)) {
+            termScore += 10;
+          }
+          entry.headingTokens.forEach((tokens, i) => {
+            if (tokens.some(matches)) {
+              termScore += 3;
+              if (heading === null) {
+                heading = entry.document.headings[i];
+              }
+            }
+          });
+          if (entry.tokens.some(matches)) {
+            termScore += 1;
+          }
+
+          score += termScore;
+          return termScore > 0;
+        });
+
+        if (matched) {
+          results.push({
+            title: entry.document.title,
+            description: entry.document.description,
+            url: heading ? entry.document.url + "#" + heading.id : entry.document.url,
+            heading: heading ? heading.title : null,
+            score,
+          });
+        }
+      }
+
+      return results.sort((a, b) => b.score - a.score);
+    }
+  }
+
+  function load(url) {
+    return fetch(url)
+      .then((response) => response.json())
+      .then((index) => new Search(index));
+  }
+
+  function bind(input) {
+    const results = document.querySelector("[data-eighty-search-results]");
+    if (!results) {
+      return;
+    }
+
+    let search = null;
+    const render = () => {
+      results.replaceChildren();
+      if (!search) {
+        return;
+      }
+
+      for (const result of search.query(input.value).slice(0, 20)) {
+        const item = document.createElement("li");
+        const link = document.createElement("a");
+        link.href = result.url;
+        link.textContent = result.heading ? result.title + " › " + result.heading : result.title;
+        item.appendChild(link);
+        if (result.description) {
+          const description = document.createElement("p");
+          description.textContent = result.description;
+          item.appendChild(description);
+        }
+        results.appendChild(item);
+      }
+    };
+
+    input.addEventListener("input", render);
+    load(input.dataset.eightySearch).then((loaded) => {
+      search = loaded;
+      render();
+    });
+  }
+
+  window.EightySearch = { load, tokenize, Search };
+
+  document.addEventListener("DOMContentLoaded", () => {
+    document.querySelectorAll("input[data-eighty-search]").forEach(bind);
+  });
+})();
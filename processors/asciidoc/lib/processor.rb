@@ -19,6 +19,22 @@ module Processor
     @@document = value
   end
 
+  def self.dependencies
+    @@dependencies ||= []
+    @@dependencies
+  end
+
+  # Record every file pulled in with `include::`.
+  module IncludeTracker
+    def resolve_include_path(target, attrlist, attributes)
+      resolved = super
+      if resolved.is_a?(Array) && resolved[1] == :file
+        Processor.dependencies.push(resolved[0])
+      end
+      resolved
+    end
+  end
+
   def self.process(source)
     Processor.read_file(source)
 
@@ -28,3 +44,5 @@ module Processor
     })
   end
 end
+
+Asciidoctor::PreprocessorReader.prepend Processor::IncludeTracker
@@ -17,6 +17,10 @@ module Processor
     nil
   end
 
+  def self.list_attribute(value)
+    (value || "").split(",").map(&:strip).reject(&:empty?)
+  end
+
   def self.read_file(path)
     options = {
       :attributes => {
@@ -29,7 +33,7 @@ module Processor
         "icons" => "font",
         "idprefix" => "",
         "idseparator" => "-",
-        "url" => "@@XREFFULLLINK:#{path}@@@",
+        "url" => "@@XREFFULLLINK:#{path}@@",
         "sectanchors" => true,
       }
     }
@@ -50,6 +54,11 @@ module Processor
     end
     item[:toc] = doc.converter.convert(doc, "outline", toclevels: 3)
     item[:created] = doc.attributes["created"]
+    item[:draft] = doc.attributes.key?("draft") && !["false", "no"].include?(doc.attributes["draft"])
+    item[:publish] = doc.attributes["publish"]
+    item[:tags] = Processor.list_attribute(doc.attributes["tags"])
+    item[:categories] = Processor.list_attribute(doc.attributes["categories"])
+    item[:layout] = doc.attributes["layout"]
 
     description_block = Processor.search_description(doc.blocks)
     item[:description] = description_block.content.tr("\n", " ")
@@ -72,6 +81,18 @@ module Processor
     end
     item[:content] = content
 
+    doc.catalog[:images].each do |image|
+      image_path = File.join(image.imagesdir || "", image.target)
+      unless image_path.include?("://")
+        Processor.dependencies.push(File.join(File.dirname(path), image_path))
+      end
+    end
+
+    base = Pathname.new(Dir.pwd)
+    item[:dependencies] = Processor.dependencies.map { |dependency|
+      Pathname.new(File.expand_path(dependency)).relative_path_from(base).to_s
+    }.select { |dependency| File.file?(dependency) }.uniq
+
     Processor.document = item
   end
 end
@@ -24,6 +24,33 @@ def parse_meta(pandoc_raw, name):
 
     return value.strip()
 
+def parse_bool(value):
+    return value is not None and value.lower() not in ("false", "no", "nil")
+
+def parse_meta_bool(pandoc_raw, name):
+    if name in pandoc_raw["meta"] and pandoc_raw["meta"][name]["t"] == "MetaBool":
+        return pandoc_raw["meta"][name]["c"]
+
+    return parse_bool(parse_meta(pandoc_raw, name))
+
+def parse_list(value):
+    if value is None:
+        return []
+
+    return [item.strip() for item in value.split(",") if item.strip()]
+
+def parse_meta_list(pandoc_raw, name):
+    if name in pandoc_raw["meta"] and pandoc_raw["meta"][name]["t"] == "MetaList":
+        items = []
+        for item in pandoc_raw["meta"][name]["c"]:
+            if item["t"] == "MetaString":
+                items.append(item["c"])
+            elif item["t"] == "MetaInlines":
+                items.append("".join(inline["c"] if inline["t"] == "Str" else " " for inline in item["c"] if inline["t"] in ("Str", "Space")))
+        return items
+
+    return parse_list(parse_meta(pandoc_raw, name))
+
 def parse_org_custom_metas(pandoc_raw):
     custom_metas = {}
 
@@ -37,6 +64,26 @@ def parse_org_custom_metas(pandoc_raw):
 
     return custom_metas
 
+def collect_dependencies(pandoc_raw, file_path):
+    dependencies = []
+
+    def walk(node):
+        if isinstance(node, dict):
+            if node.get("t") == "Image":
+                target = node["c"][2][0]
+                if not "://" in target and not target.startswith("/"):
+                    path = os.path.normpath(os.path.join(os.path.dirname(file_path), target))
+                    if os.path.isfile(path) and not path in dependencies:
+                        dependencies.append(path)
+            for value in node.values():
+                walk(value)
+        elif isinstance(node, list):
+            for value in node:
+                walk(value)
+
+    walk(pandoc_raw["blocks"])
+    return dependencies
+
 file_path = SOURCE
 with open(file_path, "rb") as f:
     content = f.read()
@@ -49,10 +96,20 @@ if os.path.splitext(file_path)[1] == ".md":
     document_id = parse_meta(pandoc_raw, "id")
     description = parse_meta(pandoc_raw, "subtitle")
     order = parse_meta(pandoc_raw, "order")
+    author = parse_meta(pandoc_raw, "author")
+    email = parse_meta(pandoc_raw, "email")
+    created = parse_meta(pandoc_raw, "created")
+    draft = parse_meta_bool(pandoc_raw, "draft")
+    publish = parse_meta(pandoc_raw, "publish")
+    tags = parse_meta_list(pandoc_raw, "tags")
+    categories = parse_meta_list(pandoc_raw, "categories")
+    layout = parse_meta(pandoc_raw, "layout")
 
     if not order is None:
         order = int(order)
 
+    dependencies = collect_dependencies(pandoc_raw, file_path)
+
     html = subprocess.run("pandoc --filter pandoc-sidenote -f markdown -t html {}".format(file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
     toc = subprocess.run("pandoc --toc -f markdown -t html --template {} {}".format(TOC_TEMPLATE, file_path), shell=True, check=True, capture_output=True).stdout.decode("utf-8")
 
@@ -65,6 +122,15 @@ if os.path.splitext(file_path)[1] == ".md":
         "order": order,
         "content": html,
         "toc": toc,
+        "author": author,
+        "email": email,
+        "created": created,
+        "draft": draft,
+        "publish": publish,
+        "tags": tags,
+        "categories": categories,
+        "layout": layout,
+        "dependencies": dependencies,
     }, sort_keys=True, indent=4)
 
 elif os.path.splitext(file_path)[1] == ".org":
@@ -79,10 +145,20 @@ elif os.path.splitext(file_path)[1] == ".org":
     sitemap_title = custom_metas.get("sitemap")
     license = custom_metas.get("license")
     license_code = custom_metas.get("license-code")
+    author = parse_meta(pandoc_raw, "author")
+    email = custom_metas.get("email")
+    created = custom_metas.get("created")
+    draft = parse_bool(custom_metas.get("draft"))
+    publish = custom_metas.get("publish")
+    tags = parse_list(custom_metas.get("tags"))
+    categories = parse_list(custom_metas.get("categories"))
+    layout = custom_metas.get("layout")
 
     if not order is None:
         order = int(order)
 
+    dependencies = collect_dependencies(pandoc_raw, file_path)
+
     revisions = {}
     for k in custom_metas:
         if k.startswith("revision[") and k.endswith("]"):
@@ -104,6 +180,15 @@ elif os.path.splitext(file_path)[1] == ".org":
         "revisions": revisions,
         "license": license,
         "licenseCode": license_code,
+        "author": author,
+        "email": email,
+        "created": created,
+        "draft": draft,
+        "publish": publish,
+        "tags": tags,
+        "categories": categories,
+        "layout": layout,
+        "dependencies": dependencies,
     }, sort_keys=True, indent=4)
 else:
     raise "Unknown file extension"
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{Error, document::RenderedDocument, layout, site::SiteMetadata};
+use handlebars::Handlebars;
+use std::{collections::BTreeMap, path::PathBuf};
+use tracing::error;
+
+pub const ARCHIVE_TEMPLATE: &str = "archive";
+
+/// Posts of every folder that has posts, keyed by folder labels, newest
+/// first.
+pub fn folders<'a>(
+    documents: &[&'a RenderedDocument],
+) -> BTreeMap<Vec<String>, Vec<&'a RenderedDocument>> {
+    let mut folders = BTreeMap::<Vec<String>, Vec<&RenderedDocument>>::new();
+
+    for document in documents {
+        if document.data.name.post.is_some() && !document.data.name.raw {
+            folders
+                .entry(document.data.name.labels.clone())
+                .or_default()
+                .push(document);
+        }
+    }
+
+    for posts in folders.values_mut() {
+        posts.sort_by(|a, b| b.data.name.post.cmp(&a.data.name.post));
+    }
+
+    folders
+}
+
+/// One page of a post archive.
+pub struct ArchivePage<'a> {
+    /// Folder path of the whole archive, which is the folder of the posts
+    /// or one of its year or month folders.
+    pub archive_path: PathBuf,
+    /// Year and month of a year or month archive.
+    pub year: Option<String>,
+    pub month: Option<String>,
+    /// Page number, starting from 1.
+    pub page: usize,
+    pub page_count: usize,
+    pub posts: &'a [&'a RenderedDocument],
+}
+
+impl ArchivePage<'_> {
+    /// Folder path of a page of the same archive.
+    pub fn page_folder_path(&self, page: usize) -> PathBuf {
+        if page <= 1 {
+            self.archive_path.clone()
+        } else {
+            self.archive_path.join("page").join(page.to_string())
+        }
+    }
+
+    pub fn folder_path(&self) -> PathBuf {
+        self.page_folder_path(self.page)
+    }
+
+    pub fn prev_folder_path(&self) -> Option<PathBuf> {
+        (self.page > 1).then(|| self.page_folder_path(self.page - 1))
+    }
+
+    pub fn next_folder_path(&self) -> Option<PathBuf> {
+        (self.page < self.page_count).then(|| self.page_folder_path(self.page + 1))
+    }
+}
+
+/// Split the posts of an archive into pages of `page_size` posts.
+pub fn paginate<'a>(
+    archive_path: PathBuf,
+    year: Option<String>,
+    month: Option<String>,
+    posts: &'a [&'a RenderedDocument],
+    page_size: usize,
+) -> Vec<ArchivePage<'a>> {
+    let chunks = posts.chunks(page_size.max(1)).collect::<Vec<_>>();
+    let page_count = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, posts)| ArchivePage {
+            archive_path: archive_path.clone(),
+            year: year.clone(),
+            month: month.clone(),
+            page: index + 1,
+            page_count,
+            posts,
+        })
+        .collect()
+}
+
+/// Generated pages of the post archive of every folder, keyed by output
+/// path: later pages of the folder (its first page is the folder's own
+/// document, when it has one) and year and month archives. Pages are only
+/// generated if the site has an `archive` layout. Two archives sharing a
+/// page, such as a year archive and a subfolder named after the year, are
+/// an error.
+pub fn pages(
+    site: &SiteMetadata,
+    documents: &[&RenderedDocument],
+    handlebars: &Handlebars,
+) -> Result<BTreeMap<PathBuf, String>, Error> {
+    let mut pages = BTreeMap::new();
+
+    if !handlebars.has_template(ARCHIVE_TEMPLATE) {
+        return Ok(pages);
+    }
+
+    let page_size = site.config.archive.page_size;
+
+    for (labels, posts) in folders(documents) {
+        let folder_path = labels.iter().collect::<PathBuf>();
+        let has_index = documents.iter().any(|document| {
+            document.data.name.labels == labels
+                && document.data.name.post.is_none()
+                && document.data.name.id.is_none()
+                && !document.data.name.raw
+        });
+
+        let mut years = BTreeMap::<String, Vec<&RenderedDocument>>::new();
+        let mut months = BTreeMap::<(String, String), Vec<&RenderedDocument>>::new();
+        for post in &posts {
+            if let Some(label) = &post.data.name.post {
+                let mut date = label.date.split('-');
+                if let (Some(year), Some(month)) = (date.next(), date.next()) {
+                    years.entry(year.to_string()).or_default().push(post);
+                    months
+                        .entry((year.to_string(), month.to_string()))
+                        .or_default()
+                        .push(post);
+                }
+            }
+        }
+
+        let mut archive_pages = paginate(folder_path.clone(), None, None, &posts, page_size);
+        if has_index {
+            archive_pages.remove(0);
+        }
+        for (year, year_posts) in &years {
+            archive_pages.extend(paginate(
+                folder_path.join(year),
+                Some(year.clone()),
+                None,
+                year_posts,
+                page_size,
+            ));
+        }
+        for ((year, month), month_posts) in &months {
+            archive_pages.extend(paginate(
+                folder_path.join(year).join(month),
+                Some(year.clone()),
+                Some(month.clone()),
+                month_posts,
+                page_size,
+            ));
+        }
+
+        for archive_page in archive_pages {
+            let page_path = archive_page.folder_path().join("index.html");
+            if pages.contains_key(&page_path) {
+                error!(
+                    "[{}] Archive page {:?} is generated twice",
+                    site.name.0, page_path
+                );
+                return Err(Error::PageConflict);
+            }
+
+            pages.insert(page_path, layout::archive(site, handlebars, &archive_page)?);
+        }
+    }
+
+    Ok(pages)
+}
@@ -28,12 +28,16 @@ use walkdir::WalkDir;
 #[derive(Clone, Debug)]
 pub struct AssetStore {
     pub assets: HashMap<PathBuf, Vec<u8>>,
+    /// Source path, relative to the site folder, of each asset that is not
+    /// bundled with Eighty.
+    pub sources: HashMap<PathBuf, PathBuf>,
     pub handlebars: Handlebars<'static>,
 }
 
 impl AssetStore {
     pub fn new(root_path: &Path) -> Result<AssetStore, Error> {
         let mut assets = HashMap::new();
+        let mut sources = HashMap::new();
 
         let asset_path = root_path.join("_assets");
 
@@ -49,6 +53,10 @@ impl AssetStore {
                     let rel_path = entry.path().strip_prefix(&asset_path)?;
 
                     assets.insert(rel_path.to_owned(), content);
+                    sources.insert(
+                        rel_path.to_owned(),
+                        entry.path().strip_prefix(root_path)?.to_owned(),
+                    );
                 }
             }
         }
@@ -62,6 +70,10 @@ impl AssetStore {
                 let rel_path = entry.path().strip_prefix(&static_path)?;
 
                 assets.insert(rel_path.to_owned(), content);
+                sources.insert(
+                    rel_path.to_owned(),
+                    entry.path().strip_prefix(root_path)?.to_owned(),
+                );
             }
         }
 
@@ -69,6 +81,18 @@ impl AssetStore {
         handlebars.set_strict_mode(true);
         handlebars.register_templates_directory(asset_path.join("layouts"), Default::default())?;
 
-        Ok(AssetStore { assets, handlebars })
+        Ok(AssetStore {
+            assets,
+            sources,
+            handlebars,
+        })
+    }
+
+    /// Insert an asset bundled with Eighty, unless the site provides its own
+    /// asset at the same path.
+    pub fn insert_bundled(&mut self, path: &str, content: &[u8]) {
+        self.assets
+            .entry(PathBuf::from(path))
+            .or_insert_with(|| content.to_owned());
     }
 }
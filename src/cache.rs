@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistent cache of rendered documents.
+
+use crate::{
+    Error,
+    document::{DocumentMetadata, DocumentType, RenderedData},
+    site::{SiteConfigMarkdown, SiteMetadata},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::UNIX_EPOCH,
+};
+use tracing::warn;
+
+pub const CACHE_DIR_NAME: &str = ".eighty-cache";
+
+/// Bumped whenever the serialized form of [`RenderedData`] changes.
+const CACHE_VERSION: u32 = 5;
+
+/// Counter making temporary entry file names unique within the process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    data: RenderedData,
+    /// Content hash of each dependency of the document.
+    dependencies: BTreeMap<PathBuf, String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderCache {
+    path: PathBuf,
+    /// Keys of the documents of the current build, kept by [`Self::prune`].
+    used: Arc<Mutex<HashSet<String>>>,
+}
+
+impl RenderCache {
+    /// Cache stored in the workspace folder, that is, the site folder for
+    /// a single site or the root folder for multiple sites.
+    pub fn new(workspace_path: &Path) -> Self {
+        Self {
+            path: workspace_path.join(CACHE_DIR_NAME).join("render"),
+            used: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Remove the cache of the workspace folder.
+    pub fn clean(workspace_path: &Path) -> Result<(), Error> {
+        let path = workspace_path.join(CACHE_DIR_NAME);
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+        Ok(())
+    }
+
+    /// Derive the cache key of a document from its site, source content,
+    /// the processor rendering it and the site configuration it depends on.
+    /// The key is marked as used by the current build.
+    pub fn key(&self, site: &SiteMetadata, document: &DocumentMetadata) -> Result<String, Error> {
+        let content = fs::read(&document.source_path)?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(CACHE_VERSION.to_le_bytes());
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update([0]);
+        hasher.update(site.name.0.as_bytes());
+        hasher.update([0]);
+        hasher.update(document.rel_source_path.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(processor_identity(site, &document.typ).as_bytes());
+        hasher.update([0]);
+        hasher.update(&content);
+
+        let key = format!("{:x}", hasher.finalize());
+        self.used.lock()?.insert(key.clone());
+        Ok(key)
+    }
+
+    /// Get a cached render, if none of its dependencies changed since.
+    pub fn get(&self, site: &SiteMetadata, key: &str) -> Option<RenderedData> {
+        let content = fs::read(self.entry_path(key)).ok()?;
+
+        let entry: CacheEntry = match serde_json::from_slice(&content) {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("Ignoring malformed cache entry {}: {}", key, err);
+                return None;
+            }
+        };
+
+        entry
+            .dependencies
+            .iter()
+            .all(|(dependency, hash)| file_hash(&site.source_path.join(dependency)) == *hash)
+            .then_some(entry.data)
+    }
+
+    pub fn insert(&self, site: &SiteMetadata, key: &str, data: &RenderedData) -> Result<(), Error> {
+        fs::create_dir_all(&self.path)?;
+
+        let entry = CacheEntry {
+            data: data.clone(),
+            dependencies: data
+                .dependencies
+                .iter()
+                .map(|dependency| {
+                    (
+                        dependency.clone(),
+                        file_hash(&site.source_path.join(dependency)),
+                    )
+                })
+                .collect(),
+        };
+
+        let temp_path = self.path.join(format!(
+            "{}.{}-{}.tmp",
+            key,
+            process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp_path, serde_json::to_vec(&entry)?)?;
+        fs::rename(temp_path, self.entry_path(key))?;
+
+        Ok(())
+    }
+
+    /// Remove the entries of documents that are not part of the current
+    /// build, such as deleted documents and earlier versions of changed
+    /// ones.
+    pub fn prune(&self) -> Result<(), Error> {
+        let Ok(entries) = fs::read_dir(&self.path) else {
+            return Ok(());
+        };
+        let used = self.used.lock()?;
+
+        for entry in entries {
+            let path = entry?.path();
+            let is_unused = path.extension().and_then(|v| v.to_str()) == Some("json")
+                && path
+                    .file_stem()
+                    .and_then(|v| v.to_str())
+                    .is_some_and(|key| !used.contains(key));
+            if is_unused {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{}.json", key))
+    }
+}
+
+/// Hash of a file's content, empty if it cannot be read.
+fn file_hash(path: &Path) -> String {
+    match fs::read(path) {
+        Ok(content) => format!("{:x}", Sha256::digest(content)),
+        Err(_) => String::new(),
+    }
+}
+
+/// Identify the processor of a document type together with the site
+/// configuration it reads.
+fn processor_identity(site: &SiteMetadata, typ: &DocumentType) -> String {
+    match typ {
+        DocumentType::AsciiDoc => command_identity(&site.source_path, "eighty-asciidoc"),
+        DocumentType::Markdown => match site.config.markdown {
+            SiteConfigMarkdown::Pandoc => command_identity(&site.source_path, "eighty-pandoc"),
+            SiteConfigMarkdown::Native => "native-markdown".to_string(),
+        },
+        DocumentType::Org => command_identity(&site.source_path, "eighty-pandoc"),
+        DocumentType::JsonDoc => "jsondoc".to_string(),
+        DocumentType::External(extension) => match site.config.processors.get(extension) {
+            Some(processor) => format!(
+                "{} {:?}",
+                command_identity(&site.source_path, &processor.command),
+                processor.args
+            ),
+            None => format!("external {}", extension),
+        },
+    }
+}
+
+/// Identify a command by its resolved executable, so that upgrading the
+/// processor invalidates the cache.
+fn command_identity(site_path: &Path, command: &str) -> String {
+    let resolved = if command.contains('/') {
+        Some(site_path.join(command))
+    } else {
+        env::var_os("PATH").and_then(|paths| {
+            env::split_paths(&paths)
+                .map(|dir| dir.join(command))
+                .find(|path| path.is_file())
+        })
+    };
+
+    let resolved = resolved.and_then(|path| {
+        let path = fs::canonicalize(path).ok()?;
+        let metadata = fs::metadata(&path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        Some(format!(
+            "{}:{}:{}",
+            path.display(),
+            metadata.len(),
+            modified
+        ))
+    });
+
+    match resolved {
+        Some(resolved) => format!("{} ({})", command, resolved),
+        None => command.to_string(),
+    }
+}
@@ -16,56 +16,160 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::command::sync::SyncPlan;
 use eighty::{
     Error,
-    workspace::{FullWorkspace, MetadatadWorkspace, RenderedWorkspace, SimplePostWorkspace},
+    cache::RenderCache,
+    manifest::{MANIFEST_FILE_NAME, Manifest},
+    sitemap_xml, utils,
+    workspace::{
+        FullWorkspace, MetadatadWorkspace, RenderedWorkspace, SimplePostSite, SimplePostWorkspace,
+    },
 };
-use std::{fs, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BuildOptions {
+    /// Render every document without using the render cache.
+    pub no_cache: bool,
+    /// Only print the changes that would be made to the target.
+    pub dry_run: bool,
+    /// Write a `manifest.json` in the output of each site.
+    pub manifest: bool,
+    /// Include drafts and documents scheduled for a later date.
+    pub drafts: bool,
+    /// Delete hidden files of the target that are not produced by the
+    /// build, which are otherwise kept.
+    pub delete_hidden: bool,
+}
+
+pub fn build(site_path: &Path, target_path: &Path, options: BuildOptions) -> Result<(), Error> {
+    check_target(site_path, target_path)?;
 
-pub fn build(site_path: &Path, target_path: &Path) -> Result<(), Error> {
-    let metadatad = MetadatadWorkspace::new_single(&site_path)?.0;
-    let rendered = RenderedWorkspace::new(&metadatad)?;
-    let full = FullWorkspace::new(&rendered)?;
+    let (metadatad, site_name) = MetadatadWorkspace::new_single(site_path)?;
+    let rendered = if options.no_cache {
+        RenderedWorkspace::new(&metadatad)?
+    } else {
+        RenderedWorkspace::new_with_cache(&metadatad, &RenderCache::new(site_path))?
+    };
+    let full = FullWorkspace::new(&rendered, options.drafts)?;
     let post = SimplePostWorkspace::new(&full)?;
 
-    for (site_name, site) in &post.0 {
-        let site_target_path = target_path.join(&site_name.0);
+    // Only the folder of the built site is synchronised, so that other
+    // sites built into the same target are kept. The specs site collects
+    // the specifications of all sites, so it is only built by `build_all`.
+    let site = &post.0[&site_name];
+    let manifest = manifest(site, options)?;
+    let files = site_files(site, manifest.as_deref())?;
+
+    sync(&target_path.join(&site_name.0), files, options)
+}
+
+pub fn build_all(root_path: &Path, target_path: &Path, options: BuildOptions) -> Result<(), Error> {
+    check_target(root_path, target_path)?;
 
-        for (file_path, file) in &site.files {
-            let file_target_path = site_target_path.join(file_path);
+    let metadatad = MetadatadWorkspace::new_multi(root_path)?;
+    let rendered = if options.no_cache {
+        RenderedWorkspace::new(&metadatad)?
+    } else {
+        RenderedWorkspace::new_with_cache(&metadatad, &RenderCache::new(root_path))?
+    };
+    let full = FullWorkspace::new(&rendered, options.drafts)?;
+    let post = SimplePostWorkspace::new(&full)?;
+
+    let manifests = post
+        .0
+        .iter()
+        .map(|(site_name, site)| Ok((site_name, manifest(site, options)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
 
-            fs::create_dir_all(
-                file_target_path
-                    .parent()
-                    .ok_or(Error::InvalidPathComponent)?,
-            )?;
-            fs::write(file_target_path, &file[..])?;
+    let mut files = BTreeMap::<PathBuf, &[u8]>::new();
+    for (site_name, manifest) in &manifests {
+        for (file_path, file) in site_files(&post.0[*site_name], manifest.as_deref())? {
+            files.insert(Path::new(&site_name.0).join(file_path), file);
         }
     }
 
-    Ok(())
+    let sitemap_index = sitemap_xml::index(&full);
+    if let Some(sitemap_index) = &sitemap_index {
+        files.insert(
+            PathBuf::from(sitemap_xml::SITEMAP_INDEX_FILE_NAME),
+            sitemap_index.as_bytes(),
+        );
+    }
+
+    sync(target_path, files, options)
 }
 
-pub fn build_all(root_path: &Path, target_path: &Path) -> Result<(), Error> {
-    let metadatad = MetadatadWorkspace::new_multi(&root_path)?;
-    let rendered = RenderedWorkspace::new(&metadatad)?;
-    let full = FullWorkspace::new(&rendered)?;
-    let post = SimplePostWorkspace::new(&full)?;
+fn manifest(site: &SimplePostSite, options: BuildOptions) -> Result<Option<Vec<u8>>, Error> {
+    if !options.manifest {
+        return Ok(None);
+    }
 
-    for (site_name, site) in &post.0 {
-        let site_target_path = target_path.join(&site_name.0);
+    Ok(Some(Manifest::new(site)?.to_vec()?))
+}
 
-        for (file_path, file) in &site.files {
-            let file_target_path = site_target_path.join(file_path);
+/// Files to write for a site, including its manifest if there is one.
+fn site_files<'a>(
+    site: &'a SimplePostSite,
+    manifest: Option<&'a [u8]>,
+) -> Result<BTreeMap<PathBuf, &'a [u8]>, Error> {
+    let mut files = site
+        .files
+        .iter()
+        .map(|(file_path, file)| (file_path.clone(), &file[..]))
+        .collect::<BTreeMap<_, _>>();
 
-            fs::create_dir_all(
-                file_target_path
-                    .parent()
-                    .ok_or(Error::InvalidPathComponent)?,
-            )?;
-            fs::write(file_target_path, &file[..])?;
-        }
+    if let Some(manifest) = manifest
+        && files
+            .insert(PathBuf::from(MANIFEST_FILE_NAME), manifest)
+            .is_some()
+    {
+        return Err(Error::ManifestConflict);
+    }
+
+    Ok(files)
+}
+
+/// Refuse a target that is or contains the source folder, which
+/// synchronising it could delete.
+fn check_target(source_path: &Path, target_path: &Path) -> Result<(), Error> {
+    let source_path = resolved_path(source_path)?;
+    let target = resolved_path(target_path)?;
+    if source_path.starts_with(&target) {
+        return Err(Error::TargetContainsSource {
+            target,
+            source_path,
+        });
     }
 
     Ok(())
 }
+
+fn sync(
+    target_path: &Path,
+    files: BTreeMap<PathBuf, &[u8]>,
+    options: BuildOptions,
+) -> Result<(), Error> {
+    let plan = SyncPlan::new(target_path, files, options.delete_hidden)?;
+
+    if options.dry_run {
+        plan.print();
+        Ok(())
+    } else {
+        plan.apply()
+    }
+}
+
+/// Absolute path with symlinks resolved, or only normalized if the path
+/// does not exist yet.
+fn resolved_path(path: &Path) -> Result<PathBuf, Error> {
+    match fs::canonicalize(path) {
+        Ok(path) => Ok(path),
+        Err(_) => Ok(utils::normalize_path(&std::path::absolute(path)?)),
+    }
+}
@@ -0,0 +1,312 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use eighty::{
+    Error, utils,
+    workspace::{
+        FullWorkspace, MetadatadWorkspace, RenderedWorkspace, SimplePostSite, SimplePostWorkspace,
+    },
+};
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+struct Link {
+    target: String,
+    line: usize,
+}
+
+struct BrokenLink {
+    /// Generated page containing the link, relative to the site.
+    page: PathBuf,
+    /// Line of the link in the generated page.
+    line: usize,
+    /// Document the page was generated from, if any.
+    source: Option<PathBuf>,
+    target: String,
+    reason: &'static str,
+}
+
+pub fn check(site_path: &Path) -> Result<(), Error> {
+    let metadatad = MetadatadWorkspace::new_single(site_path)?.0;
+    let rendered = RenderedWorkspace::new(&metadatad)?;
+    let full = FullWorkspace::new(&rendered, false)?;
+    let post = SimplePostWorkspace::new(&full)?;
+
+    let mut broken = Vec::new();
+
+    let mut site_names = post.keys().collect::<Vec<_>>();
+    site_names.sort();
+
+    for site_name in site_names {
+        let site = &post[site_name];
+
+        let mut sources = HashMap::new();
+        if let Some(full_site) = full.sites.get(site_name) {
+            for document in &full_site.documents {
+                sources.insert(
+                    document.rendered.name.path(),
+                    document.metadata.rel_source_path.clone(),
+                );
+            }
+        }
+
+        let pages = site
+            .files
+            .iter()
+            .filter(|(path, _)| is_html(path))
+            .filter_map(|(path, content)| {
+                str::from_utf8(content)
+                    .ok()
+                    .map(|content| (path.clone(), content))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut ids = HashMap::new();
+        for (path, content) in &pages {
+            ids.insert(path.clone(), page_ids(content)?);
+        }
+
+        let mut page_paths = pages.keys().collect::<Vec<_>>();
+        page_paths.sort();
+
+        for page_path in page_paths {
+            for link in page_links(pages[page_path])? {
+                if let Some(reason) = check_link(site, &ids, page_path, &link.target) {
+                    broken.push(BrokenLink {
+                        page: page_path.clone(),
+                        line: link.line,
+                        source: sources.get(page_path).cloned(),
+                        target: link.target,
+                        reason,
+                    });
+                }
+            }
+        }
+    }
+
+    for link in &broken {
+        let source = link
+            .source
+            .as_ref()
+            .map(|source| format!(" (from {})", source.display()))
+            .unwrap_or_default();
+        println!(
+            "{}:{}: broken link {:?}: {}{}",
+            link.page.display(),
+            link.line,
+            link.target,
+            link.reason,
+            source
+        );
+    }
+
+    if broken.is_empty() {
+        info!("[check] no broken links found");
+        Ok(())
+    } else {
+        Err(Error::BrokenLinks)
+    }
+}
+
+fn check_link(
+    site: &SimplePostSite,
+    ids: &HashMap<PathBuf, HashSet<String>>,
+    page_path: &Path,
+    target: &str,
+) -> Option<&'static str> {
+    if target.contains("@@") {
+        return Some("unresolved variable");
+    }
+
+    if target.is_empty()
+        || target.starts_with("//")
+        || target.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && !scheme.contains('/')
+                && !scheme.contains('#')
+                && !scheme.contains('?')
+        })
+    {
+        return None;
+    }
+
+    let (target, fragment) = match target.split_once('#') {
+        Some((target, fragment)) => (target, Some(fragment)),
+        None => (target, None),
+    };
+    let target = target.split('?').next().unwrap_or_default();
+    let target = percent_decode(target);
+
+    let target_path = if target.is_empty() {
+        page_path.to_owned()
+    } else if let Some(absolute) = target.strip_prefix('/') {
+        match format!("/{}", absolute).strip_prefix(&site.base_url) {
+            Some(rel) => utils::normalize_path(Path::new(rel)),
+            None => return None,
+        }
+    } else {
+        utils::normalize_path(&page_path.parent().unwrap_or(Path::new("")).join(&target))
+    };
+
+    if target_path.starts_with("..") {
+        return Some("target outside of site");
+    }
+
+    let resolved = if site.files.contains_key(&target_path) {
+        target_path
+    } else {
+        let index_path = target_path.join("index.html");
+        if site.files.contains_key(&index_path) {
+            index_path
+        } else {
+            return Some("target not found");
+        }
+    };
+
+    if let Some(fragment) = fragment
+        && !fragment.is_empty()
+        && let Some(target_ids) = ids.get(&resolved)
+        && !target_ids.contains(&percent_decode(fragment))
+    {
+        return Some("fragment not found");
+    }
+
+    None
+}
+
+fn is_html(path: &Path) -> bool {
+    path.extension().and_then(|v| v.to_str()) == Some("html")
+}
+
+fn page_ids(content: &str) -> Result<HashSet<String>, Error> {
+    let ids = RefCell::new(HashSet::new());
+
+    rewrite_str(
+        content,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!("[id]", |el| {
+                    if let Some(id) = el.get_attribute("id") {
+                        ids.borrow_mut().insert(id);
+                    }
+                    Ok(())
+                }),
+                element!("a[name]", |el| {
+                    if let Some(name) = el.get_attribute("name") {
+                        ids.borrow_mut().insert(name);
+                    }
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+
+    Ok(ids.into_inner())
+}
+
+fn page_links(content: &str) -> Result<Vec<Link>, Error> {
+    let links = RefCell::new(Vec::new());
+    let line_starts = line_starts(content);
+
+    rewrite_str(
+        content,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("[href], [src]", |el| {
+                let line = line_of(&line_starts, el.source_location().bytes().start);
+                for attribute in ["href", "src"] {
+                    if let Some(target) = el.get_attribute(attribute) {
+                        links.borrow_mut().push(Link { target, line });
+                    }
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+
+    Ok(links.into_inner())
+}
+
+/// Byte offsets at which each line of `content` starts.
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
+
+/// One-based line containing the byte at `offset`.
+fn line_of(line_starts: &[usize], offset: usize) -> usize {
+    match line_starts.binary_search(&offset) {
+        Ok(index) => index + 1,
+        Err(index) => index,
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = s.get(i + 1..i + 3)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_of_offsets() {
+        let starts = line_starts("a\nbc\n\nd");
+        assert_eq!(starts, vec![0, 2, 5, 6]);
+        assert_eq!(line_of(&starts, 0), 1);
+        assert_eq!(line_of(&starts, 1), 1);
+        assert_eq!(line_of(&starts, 2), 2);
+        assert_eq!(line_of(&starts, 4), 2);
+        assert_eq!(line_of(&starts, 5), 3);
+        assert_eq!(line_of(&starts, 7), 4);
+    }
+
+    #[test]
+    fn page_links_lines() {
+        let links = page_links("<p>\n<a href=\"a\">a</a>\n\n<img src=\"b\">").unwrap();
+        let links = links
+            .iter()
+            .map(|link| (link.target.as_str(), link.line))
+            .collect::<Vec<_>>();
+        assert_eq!(links, vec![("a", 2), ("b", 4)]);
+    }
+}
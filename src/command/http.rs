@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! HTTP helpers for the development server.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::path::Path;
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Content type of a served file, from its extension.
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .map(|v| v.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "jsonld" => "application/ld+json",
+        "webmanifest" => "application/manifest+json",
+        "xml" => "application/xml",
+        "atom" => "application/atom+xml",
+        "rss" => "application/rss+xml",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "ics" => "text/calendar; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "eot" => "application/vnd.ms-fontobject",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn format_http_date(date: DateTime<Utc>) -> String {
+    date.format(HTTP_DATE_FORMAT).to_string()
+}
+
+pub fn parse_http_date(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s.trim(), HTTP_DATE_FORMAT)
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+/// Whether an `If-None-Match` header matches an entity tag.
+pub fn etag_matches(header: &str, etag: &str) -> bool {
+    header.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// Parse a `Range` header for a body of `len` bytes into an inclusive byte
+/// range. Returns `None` if the header should be ignored, and `Some(None)` if
+/// the range cannot be satisfied. Only single ranges are supported.
+pub fn parse_range(header: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        let suffix = end.parse::<u64>().ok()?;
+        if suffix == 0 || len == 0 {
+            None
+        } else {
+            Some((len.saturating_sub(suffix), len - 1))
+        }
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() {
+            u64::MAX
+        } else {
+            end.parse::<u64>().ok()?
+        };
+
+        if start > end {
+            return None;
+        }
+        if start >= len {
+            None
+        } else {
+            Some((start, end.min(len - 1)))
+        }
+    };
+
+    Some(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(parse_range("bytes=0-499", 1000), Some(Some((0, 499))));
+        assert_eq!(parse_range(" bytes=500-999 ", 1000), Some(Some((500, 999))));
+        assert_eq!(parse_range("bytes=500-", 1000), Some(Some((500, 999))));
+        assert_eq!(parse_range("bytes=900-2000", 1000), Some(Some((900, 999))));
+        assert_eq!(parse_range("bytes=0-0", 1), Some(Some((0, 0))));
+    }
+
+    #[test]
+    fn parse_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-500", 1000), Some(Some((500, 999))));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some(Some((0, 999))));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(None));
+        assert_eq!(parse_range("bytes=-10", 0), Some(None));
+    }
+
+    #[test]
+    fn parse_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(None));
+        assert_eq!(parse_range("bytes=1000-1999", 1000), Some(None));
+        assert_eq!(parse_range("bytes=0-", 0), Some(None));
+    }
+
+    #[test]
+    fn parse_invalid_ranges() {
+        assert_eq!(parse_range("bytes=500-100", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("bytes=-", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+        assert_eq!(parse_range("bytes=0", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("", 1000), None);
+    }
+
+    #[test]
+    fn etags() {
+        assert!(etag_matches("\"a\"", "\"a\""));
+        assert!(etag_matches("\"b\", W/\"a\"", "\"a\""));
+        assert!(etag_matches("*", "\"a\""));
+        assert!(!etag_matches("\"b\"", "\"a\""));
+    }
+
+    #[test]
+    fn http_dates() {
+        let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(format_http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Live reload of pages served by `serve`, through Server-Sent Events.
+
+use eighty::Error;
+use handlebars::html_escape;
+use hyper::{Body, Response};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+pub const EVENTS_PATH: &str = "/__eighty/events";
+pub const SCRIPT_PATH: &str = "/__eighty/live-reload.js";
+pub const SCRIPT: &str = include_str!("../../assets/js/eighty-live-reload.js");
+
+#[derive(Clone, Debug)]
+pub enum LiveReloadEvent {
+    /// The workspace was rebuilt successfully.
+    Reload,
+    /// Rebuilding the workspace failed.
+    BuildError(String),
+}
+
+impl LiveReloadEvent {
+    fn to_sse(&self) -> Result<String, Error> {
+        Ok(match self {
+            Self::Reload => "event: reload\ndata: \n\n".to_string(),
+            Self::BuildError(message) => format!(
+                "event: build-error\ndata: {}\n\n",
+                serde_json::to_string(message)?
+            ),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LiveReload {
+    sender: broadcast::Sender<LiveReloadEvent>,
+    /// Error of the last rebuild, if it failed, shown on every page until
+    /// the next successful rebuild.
+    error: Arc<RwLock<Option<String>>>,
+}
+
+impl LiveReload {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(16).0,
+            error: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Notify every connected page. Having no connected page is not an
+    /// error.
+    pub fn notify(&self, event: LiveReloadEvent) -> Result<(), Error> {
+        *self.error.write()? = match &event {
+            LiveReloadEvent::Reload => None,
+            LiveReloadEvent::BuildError(message) => Some(message.clone()),
+        };
+
+        let _ = self.sender.send(event);
+        Ok(())
+    }
+
+    /// Stream of live reload events for one page.
+    pub fn events(&self) -> Result<Response<Body>, Error> {
+        let mut receiver = self.sender.subscribe();
+        let (mut sender, body) = Body::channel();
+
+        tokio::spawn(async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => LiveReloadEvent::Reload,
+                    Err(RecvError::Closed) => break,
+                };
+
+                let Ok(data) = event.to_sse() else {
+                    continue;
+                };
+                if sender.send_data(data.into()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::builder()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .body(body)?)
+    }
+
+    pub fn script(&self) -> Result<Response<Body>, Error> {
+        Ok(Response::builder()
+            .header("Content-Type", "text/javascript")
+            .body(SCRIPT.into())?)
+    }
+
+    /// Insert the live reload script at the end of an HTML page, along with
+    /// the error of the last rebuild if it failed. Also returns whether such
+    /// an error was inserted, in which case the page no longer matches the
+    /// validators of the built file.
+    pub fn inject(&self, content: &[u8]) -> Result<(Vec<u8>, bool), Error> {
+        let error = self.error.read()?;
+        let script = match error.as_ref() {
+            Some(message) => format!(
+                "<script src=\"{}\" data-build-error=\"{}\"></script>",
+                SCRIPT_PATH,
+                html_escape(message)
+            ),
+            None => format!("<script src=\"{}\"></script>", SCRIPT_PATH),
+        };
+
+        let index = content
+            .windows(b"</body>".len())
+            .rposition(|window| window.eq_ignore_ascii_case(b"</body>"))
+            .unwrap_or(content.len());
+
+        let mut injected = Vec::with_capacity(content.len() + script.len());
+        injected.extend_from_slice(&content[..index]);
+        injected.extend_from_slice(script.as_bytes());
+        injected.extend_from_slice(&content[index..]);
+        Ok((injected, error.is_some()))
+    }
+}
@@ -17,4 +17,8 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 pub mod build;
+pub mod check;
+pub mod http;
+pub mod live_reload;
 pub mod serve;
+pub mod sync;
@@ -16,20 +16,30 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+use crate::command::http;
+use crate::command::live_reload::{self, LiveReload, LiveReloadEvent};
+use chrono::{DateTime, Timelike, Utc};
 use eighty::{
-    Error,
+    Error, layout,
     site::SiteName,
-    workspace::{FullWorkspace, MetadatadWorkspace, RenderedWorkspace, SimplePostWorkspace},
+    specs::SPECS_SITE_NAME,
+    workspace::{
+        FullWorkspace, MetadatadWorkspace, RenderedWorkspace, SimplePostSite, SimplePostWorkspace,
+        WorkspacePath,
+    },
 };
 use hyper::{
-    Body, Request, Response, Server, StatusCode,
+    Body, Method, Request, Response, Server, StatusCode, header,
     service::{make_service_fn, service_fn},
 };
 use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
 use std::{
+    cmp::Reverse,
+    collections::HashMap,
     fs,
-    net::SocketAddr,
-    path::Path,
+    net::TcpListener,
+    path::{Path, PathBuf},
     sync::{Arc, RwLock, mpsc::channel},
     thread,
     time::Duration,
@@ -40,58 +50,220 @@ pub struct Context {
     #[allow(unused)]
     pub metadatad: MetadatadWorkspace,
     pub rendered: RenderedWorkspace,
-    #[allow(unused)]
     pub full: FullWorkspace,
     pub post: SimplePostWorkspace,
-    pub site_name: SiteName,
+    pub versions: HashMap<SiteName, HashMap<PathBuf, FileVersion>>,
+}
+
+/// Validators of a served file, kept across rebuilds while its content is
+/// unchanged.
+#[derive(Clone, Debug)]
+pub struct FileVersion {
+    pub etag: String,
+    pub modified: DateTime<Utc>,
 }
 
+/// Number of consecutive ports tried when the requested one is taken.
+const PORT_ATTEMPTS: u16 = 10;
+
 async fn handle(
     req: Request<Body>,
     context: Arc<RwLock<Context>>,
+    live_reload: LiveReload,
 ) -> Result<Response<Body>, Error> {
-    let context = context.read()?;
-    let site = context
-        .post
-        .get(&context.site_name)
-        .ok_or(Error::SiteNotExist)?;
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return Ok(Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(header::ALLOW, "GET, HEAD")
+            .body(Body::empty())?);
+    }
+
+    match req.uri().path() {
+        live_reload::EVENTS_PATH => return live_reload.events(),
+        live_reload::SCRIPT_PATH => return live_reload.script(),
+        _ => (),
+    }
 
+    let context = context.read()?;
     let uri_path = Path::new(req.uri().path());
-    let rel_path = uri_path.strip_prefix(&site.base_url)?;
-    let index_rel_path = rel_path.join("index.html");
-
-    let content = site
-        .files
-        .get(&rel_path.to_owned())
-        .map(|p| (rel_path, p))
-        .or(site
-            .files
-            .get(&index_rel_path.to_owned())
-            .map(|p| (index_rel_path.as_ref(), p)));
-
-    if let Some((content_path, content)) = content {
-        let mut response = Response::builder();
-
-        if content_path.extension().and_then(|v| v.to_str()) == Some("html") {
-            response = response.header("Content-Type", "text/html");
+    let sites = matching_sites(&context.post, uri_path);
+
+    let content = sites.iter().find_map(|(site_name, site, rel_path)| {
+        let index_rel_path = rel_path.join("index.html");
+
+        site.files
+            .get(*rel_path)
+            .map(|p| (*site_name, rel_path.to_path_buf(), p))
+            .or(site
+                .files
+                .get(&index_rel_path)
+                .map(|p| (*site_name, index_rel_path, p)))
+    });
+
+    if let Some((site_name, content_path, content)) = content {
+        let content_type = http::content_type(&content_path);
+        let version = context
+            .versions
+            .get(site_name)
+            .and_then(|versions| versions.get(&content_path));
+        let (content, version) = if content_type.starts_with("text/html") {
+            let (content, build_error) = live_reload.inject(content)?;
+            // A page showing a build error must not be revalidated against
+            // the unchanged built file, or clients keep their cached copy.
+            (content, version.filter(|_| !build_error))
+        } else {
+            (content.clone(), version)
+        };
+
+        respond(&req, content_type, content, version)
+    } else {
+        let page = sites
+            .first()
+            .and_then(|(site_name, _, _)| context.full.sites.get(*site_name))
+            .map(|site| layout::not_found(&site.site, &site.assets.handlebars, req.uri().path()))
+            .transpose()?
+            .flatten();
+
+        let response = Response::builder().status(StatusCode::NOT_FOUND);
+        Ok(match page {
+            Some(page) => response
+                .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+                .body(live_reload.inject(page.as_bytes())?.0.into())?,
+            None => response
+                .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body("Not found".into())?,
+        })
+    }
+}
+
+/// Respond with a file, honouring conditional, range and `HEAD` requests.
+fn respond(
+    req: &Request<Body>,
+    content_type: &str,
+    content: Vec<u8>,
+    version: Option<&FileVersion>,
+) -> Result<Response<Body>, Error> {
+    let header_str = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "no-cache");
+
+    if let Some(version) = version {
+        response = response.header(header::ETAG, &version.etag).header(
+            header::LAST_MODIFIED,
+            http::format_http_date(version.modified),
+        );
+
+        let not_modified = match header_str(header::IF_NONE_MATCH) {
+            Some(if_none_match) => http::etag_matches(if_none_match, &version.etag),
+            None => header_str(header::IF_MODIFIED_SINCE)
+                .and_then(http::parse_http_date)
+                .is_some_and(|since| version.modified <= since),
+        };
+        if not_modified {
+            return Ok(response
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())?);
         }
+    }
 
-        return Ok(response.body(content.clone().into())?);
+    let len = content.len() as u64;
+    let if_range_matches = match header_str(header::IF_RANGE) {
+        Some(if_range) => version.is_some_and(|version| if_range.trim() == version.etag),
+        None => true,
+    };
+    let range = header_str(header::RANGE)
+        .filter(|_| if_range_matches)
+        .and_then(|range| http::parse_range(range, len));
+
+    let (response, content) = match range {
+        None => (response.status(StatusCode::OK), content),
+        Some(Some((start, end))) => (
+            response.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, len),
+            ),
+            content[start as usize..=end as usize].to_vec(),
+        ),
+        Some(None) => {
+            return Ok(response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Body::empty())?);
+        }
+    };
+
+    let response = response.header(header::CONTENT_LENGTH, content.len());
+    if req.method() == Method::HEAD {
+        Ok(response.body(Body::empty())?)
     } else {
-        return Ok(Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body("Not found".into())?);
+        Ok(response.body(content.into())?)
+    }
+}
+
+/// Sites whose `base_url` is a prefix of a request path, with the path
+/// relative to the site. Sites with a longer `base_url` come first.
+fn matching_sites<'a, 'b>(
+    post: &'a SimplePostWorkspace,
+    uri_path: &'b Path,
+) -> Vec<(&'a SiteName, &'a SimplePostSite, &'b Path)> {
+    let mut sites = post
+        .iter()
+        .filter_map(|(name, site)| {
+            let rel_path = uri_path.strip_prefix(&site.base_url).ok()?;
+            Some((name, site, rel_path))
+        })
+        .collect::<Vec<_>>();
+    sites.sort_by_key(|(name, site, _)| {
+        (
+            Reverse(site.base_url.len()),
+            name.0 == SPECS_SITE_NAME,
+            (*name).clone(),
+        )
+    });
+
+    sites
+}
+
+/// Bind to `host:port`, or to one of the following ports if it is taken.
+fn bind(host: &str, port: u16) -> Result<TcpListener, Error> {
+    let mut attempt = 0;
+
+    loop {
+        let candidate = port.saturating_add(attempt);
+        match TcpListener::bind((host, candidate)) {
+            Ok(listener) => return Ok(listener),
+            Err(err)
+                if err.kind() == std::io::ErrorKind::AddrInUse
+                    && attempt + 1 < PORT_ATTEMPTS
+                    && candidate < u16::MAX =>
+            {
+                info!(
+                    "[server] port {} is taken, trying {}",
+                    candidate,
+                    candidate + 1
+                );
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
     }
 }
 
 #[tokio::main]
-pub async fn serve(site_path: &Path) -> Result<(), Error> {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
+pub async fn serve(workspace_path: WorkspacePath, host: &str, port: u16) -> Result<(), Error> {
+    let listener = bind(host, port)?;
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
 
-    let context = Arc::new(RwLock::new(async_build(site_path).await?));
+    let context = Arc::new(RwLock::new(async_build(&workspace_path).await?));
 
-    let site_path = site_path.to_owned();
+    let site_path = workspace_path.path().to_owned();
     let watch_context = context.clone();
+    let live_reload = LiveReload::new();
+    let watch_live_reload = live_reload.clone();
 
     thread::spawn(move || {
         let watching = || -> Result<(), Error> {
@@ -100,36 +272,68 @@ pub async fn serve(site_path: &Path) -> Result<(), Error> {
             let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(2))?;
             watcher.watch(site_path.clone(), RecursiveMode::Recursive)?;
 
+            let should_rebuild = |path: &Path| match should_rebuild_for_path(path, &site_path) {
+                Ok(should_rebuild) => should_rebuild,
+                Err(err) => {
+                    error!("[workspace] cannot check changed path {:?}: {}", path, err);
+                    false
+                }
+            };
+
             loop {
-                match rx.recv() {
-                    Ok(event) => {
-                        let should_rebuild = match event {
-                            DebouncedEvent::NoticeWrite(path)
-                            | DebouncedEvent::NoticeRemove(path)
-                            | DebouncedEvent::Create(path)
-                            | DebouncedEvent::Write(path)
-                            | DebouncedEvent::Chmod(path)
-                            | DebouncedEvent::Remove(path) => {
-                                should_rebuild_for_path(&path, &site_path)?
-                            }
-                            DebouncedEvent::Rename(p1, p2) => {
-                                should_rebuild_for_path(&p1, &site_path)?
-                                    || should_rebuild_for_path(&p2, &site_path)?
-                            }
-                            DebouncedEvent::Rescan => true,
-                            DebouncedEvent::Error(err, _) => {
-                                return Err(Error::Notify { source: err });
-                            }
-                        };
-
-                        if should_rebuild {
-                            let mut context = watch_context.write()?;
-                            *context = build(&site_path, Some(&context))?;
-
-                            info!("[workspace] rebuilt after source folder changes");
-                        }
+                let event = match rx.recv() {
+                    Ok(event) => event,
+                    // The watcher owns the sender, so this only happens once
+                    // it is gone.
+                    Err(e) => {
+                        error!("watch error: {:?}", e);
+                        break Ok(());
                     }
-                    Err(e) => error!("watch error: {:?}", e),
+                };
+
+                let should_rebuild = match event {
+                    DebouncedEvent::NoticeWrite(path)
+                    | DebouncedEvent::NoticeRemove(path)
+                    | DebouncedEvent::Create(path)
+                    | DebouncedEvent::Write(path)
+                    | DebouncedEvent::Chmod(path)
+                    | DebouncedEvent::Remove(path) => should_rebuild(&path),
+                    DebouncedEvent::Rename(p1, p2) => should_rebuild(&p1) || should_rebuild(&p2),
+                    DebouncedEvent::Rescan => true,
+                    DebouncedEvent::Error(err, path) => {
+                        error!("watch error for {:?}: {}", path, err);
+                        continue;
+                    }
+                };
+                if !should_rebuild {
+                    continue;
+                }
+
+                let mut context = match watch_context.write() {
+                    Ok(context) => context,
+                    Err(err) => {
+                        error!("[workspace] cannot lock the served build: {}", err);
+                        continue;
+                    }
+                };
+                let event = match build(&workspace_path, Some(&context)) {
+                    Ok(new_context) => {
+                        *context = new_context;
+                        info!("[workspace] rebuilt after source folder changes");
+                        LiveReloadEvent::Reload
+                    }
+                    Err(err) => {
+                        error!(
+                            "[workspace] rebuild failed, serving the last good build: {}",
+                            err
+                        );
+                        LiveReloadEvent::BuildError(err.to_string())
+                    }
+                };
+                drop(context);
+
+                if let Err(err) = watch_live_reload.notify(event) {
+                    error!("[workspace] cannot notify live reload clients: {}", err);
                 }
             }
         };
@@ -142,13 +346,18 @@ pub async fn serve(site_path: &Path) -> Result<(), Error> {
 
     let make_svc = make_service_fn(move |_conn| {
         let context = context.clone();
+        let live_reload = live_reload.clone();
 
-        async move { Ok::<_, Error>(service_fn(move |req| handle(req, context.clone()))) }
+        async move {
+            Ok::<_, Error>(service_fn(move |req| {
+                handle(req, context.clone(), live_reload.clone())
+            }))
+        }
     });
 
-    let server = Server::bind(&addr).serve(make_svc);
+    let server = Server::from_tcp(listener)?.serve(make_svc);
 
-    info!("[server] listening on port 8000");
+    info!("[server] listening on http://{}", addr);
     if let Err(e) = server.await {
         error!("server error: {}", e);
     }
@@ -156,37 +365,70 @@ pub async fn serve(site_path: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-async fn async_build(site_path: &Path) -> Result<Context, Error> {
-    let site_path = site_path.to_owned();
+async fn async_build(workspace_path: &WorkspacePath) -> Result<Context, Error> {
+    let workspace_path = workspace_path.clone();
 
     let context =
-        tokio::task::spawn_blocking(move || -> Result<_, Error> { build(&site_path, None) })
+        tokio::task::spawn_blocking(move || -> Result<_, Error> { build(&workspace_path, None) })
             .await??;
 
     Ok(context)
 }
 
-fn build(site_path: &Path, old: Option<&Context>) -> Result<Context, Error> {
-    let (metadatad, site_name) = MetadatadWorkspace::new_single(&site_path)?;
+fn build(workspace_path: &WorkspacePath, old: Option<&Context>) -> Result<Context, Error> {
+    let metadatad = match workspace_path {
+        WorkspacePath::Root(root_path) => MetadatadWorkspace::new_multi(root_path)?,
+        WorkspacePath::Site(site_path) => MetadatadWorkspace::new_single(site_path)?.0,
+    };
     let rendered = if let Some(old) = old {
         RenderedWorkspace::new_with_old(&metadatad, &old.rendered)?
     } else {
         RenderedWorkspace::new(&metadatad)?
     };
-    let full = FullWorkspace::new(&rendered)?;
+    let full = FullWorkspace::new(&rendered, true)?;
     let post = SimplePostWorkspace::new(&full)?;
+    let versions = file_versions(&post, old.map(|old| &old.versions));
 
     let context = Context {
         metadatad,
         rendered,
         full,
         post,
-        site_name,
+        versions,
     };
 
     Ok(context)
 }
 
+fn file_versions(
+    post: &SimplePostWorkspace,
+    old: Option<&HashMap<SiteName, HashMap<PathBuf, FileVersion>>>,
+) -> HashMap<SiteName, HashMap<PathBuf, FileVersion>> {
+    let now = Utc::now().with_nanosecond(0).unwrap_or_else(Utc::now);
+
+    post.iter()
+        .map(|(site_name, site)| {
+            let old_site = old.and_then(|old| old.get(site_name));
+            let versions = site
+                .files
+                .iter()
+                .map(|(path, content)| {
+                    let etag = format!("\"{:x}\"", Sha256::digest(content));
+                    let modified = old_site
+                        .and_then(|old_site| old_site.get(path))
+                        .filter(|old_version| old_version.etag == etag)
+                        .map(|old_version| old_version.modified)
+                        .unwrap_or(now);
+
+                    (path.clone(), FileVersion { etag, modified })
+                })
+                .collect();
+
+            (site_name.clone(), versions)
+        })
+        .collect()
+}
+
 fn should_rebuild_for_path(path: &Path, root_path: &Path) -> Result<bool, Error> {
     let root_path = fs::canonicalize(root_path)?;
 
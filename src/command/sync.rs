@@ -0,0 +1,343 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! Synchronisation of a build output directory with the built files.
+
+use eighty::Error;
+use rayon::prelude::*;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use walkdir::WalkDir;
+
+/// Suffix of the temporary files written before being renamed into place.
+const TEMP_SUFFIX: &str = ".eighty-tmp";
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+enum SyncAction {
+    Add,
+    Change,
+    Delete,
+}
+
+impl SyncAction {
+    fn symbol(self) -> char {
+        match self {
+            Self::Add => '+',
+            Self::Change => '~',
+            Self::Delete => '-',
+        }
+    }
+}
+
+/// Changes needed to make an output directory contain exactly a set of
+/// files. Files whose content is already up to date are left untouched, so
+/// that their modification times are preserved. Hidden files, such as
+/// `.git` or `.nojekyll`, are only deleted if requested.
+pub struct SyncPlan<'a> {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, &'a [u8]>,
+    actions: BTreeMap<PathBuf, SyncAction>,
+}
+
+impl<'a> SyncPlan<'a> {
+    /// Plan the synchronisation of `root`, where `files` are relative to
+    /// `root`. Hidden files not in `files` are kept unless `delete_hidden`
+    /// is set.
+    pub fn new(
+        root: &Path,
+        files: BTreeMap<PathBuf, &'a [u8]>,
+        delete_hidden: bool,
+    ) -> Result<Self, Error> {
+        let mut actions = files
+            .par_iter()
+            .map(|(rel_path, content)| {
+                let action = match fs::read(root.join(rel_path)) {
+                    Ok(existing) if existing == *content => None,
+                    Ok(_) => Some(SyncAction::Change),
+                    // A directory at the path, or a file at one of its
+                    // parents, is replaced by the new file.
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::NotFound
+                                | io::ErrorKind::IsADirectory
+                                | io::ErrorKind::NotADirectory
+                        ) =>
+                    {
+                        Some(SyncAction::Add)
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                Ok(action.map(|action| (rel_path.clone(), action)))
+            })
+            .collect::<Vec<Result<_, Error>>>()
+            .into_iter()
+            .filter_map(Result::transpose)
+            .collect::<Result<BTreeMap<_, _>, Error>>()?;
+
+        if root.exists() {
+            let entries = WalkDir::new(root).into_iter().filter_entry(|entry| {
+                delete_hidden
+                    || entry.depth() == 0
+                    || !entry.file_name().to_string_lossy().starts_with('.')
+            });
+            for entry in entries {
+                let entry = entry?;
+
+                if !entry.file_type().is_dir() {
+                    let rel_path = entry.path().strip_prefix(root)?;
+                    if !files.contains_key(rel_path) {
+                        actions.insert(rel_path.to_owned(), SyncAction::Delete);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            root: root.to_owned(),
+            files,
+            actions,
+        })
+    }
+
+    /// Print the planned additions, changes and deletions.
+    pub fn print(&self) {
+        for (rel_path, action) in &self.actions {
+            println!("{} {}", action.symbol(), self.root.join(rel_path).display());
+        }
+    }
+
+    /// Apply the plan. Deletions are applied first, along with the removal
+    /// of the directories they leave empty, so that a file can replace a
+    /// directory and the other way around. Files are then written to a
+    /// temporary file and renamed into place.
+    pub fn apply(&self) -> Result<(), Error> {
+        self.actions
+            .par_iter()
+            .filter(|(_, action)| **action == SyncAction::Delete)
+            .map(|(rel_path, _)| Ok(fs::remove_file(self.root.join(rel_path))?))
+            .collect::<Vec<Result<(), Error>>>()
+            .into_iter()
+            .collect::<Result<(), Error>>()?;
+
+        for (rel_path, action) in &self.actions {
+            if *action == SyncAction::Delete {
+                remove_empty_parents(&self.root, &self.root.join(rel_path));
+            }
+        }
+
+        self.actions
+            .par_iter()
+            .filter(|(_, action)| **action != SyncAction::Delete)
+            .map(|(rel_path, _)| write_atomic(&self.root.join(rel_path), self.files[rel_path]))
+            .collect::<Vec<Result<(), Error>>>()
+            .into_iter()
+            .collect::<Result<(), Error>>()?;
+
+        let count = |expected| self.actions.values().filter(|v| **v == expected).count();
+        info!(
+            "[build] {}: {} added, {} changed, {} deleted, {} unchanged",
+            self.root.display(),
+            count(SyncAction::Add),
+            count(SyncAction::Change),
+            count(SyncAction::Delete),
+            self.files.len() - count(SyncAction::Add) - count(SyncAction::Change),
+        );
+
+        Ok(())
+    }
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> Result<(), Error> {
+    let parent = path.parent().ok_or(Error::InvalidPathComponent)?;
+    fs::create_dir_all(parent)?;
+
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().ok_or(Error::InvalidPathComponent)?);
+    temp_name.push(TEMP_SUFFIX);
+    let temp_path = parent.join(temp_name);
+
+    fs::write(&temp_path, content)?;
+    fs::rename(temp_path, path)?;
+
+    Ok(())
+}
+
+/// Remove the empty directories between a deleted file and `root`.
+fn remove_empty_parents(root: &Path, path: &Path) {
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if dir == root || !dir.starts_with(root) || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    /// Empty directory unique to a test, removed when dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("eighty-sync-{}-{}", process::id(), name));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, rel_path: &str, content: &str) {
+            let path = self.0.join(rel_path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn files<'a>(files: &[(&str, &'a str)]) -> BTreeMap<PathBuf, &'a [u8]> {
+        files
+            .iter()
+            .map(|(path, content)| (PathBuf::from(path), content.as_bytes()))
+            .collect()
+    }
+
+    fn actions<'a>(plan: &'a SyncPlan) -> Vec<(&'a str, SyncAction)> {
+        plan.actions
+            .iter()
+            .map(|(path, action)| (path.to_str().unwrap(), *action))
+            .collect()
+    }
+
+    #[test]
+    fn plan_actions() {
+        let dir = TestDir::new("plan");
+        dir.write("same.html", "same");
+        dir.write("changed.html", "old");
+        dir.write("old/gone.html", "gone");
+
+        let plan = SyncPlan::new(
+            &dir.0,
+            files(&[
+                ("same.html", "same"),
+                ("changed.html", "new"),
+                ("new/added.html", "added"),
+            ]),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            actions(&plan),
+            vec![
+                ("changed.html", SyncAction::Change),
+                ("new/added.html", SyncAction::Add),
+                ("old/gone.html", SyncAction::Delete),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_missing_root() {
+        let dir = TestDir::new("missing");
+        let root = dir.0.join("out");
+
+        let plan = SyncPlan::new(&root, files(&[("index.html", "index")]), false).unwrap();
+
+        assert_eq!(actions(&plan), vec![("index.html", SyncAction::Add)]);
+    }
+
+    #[test]
+    fn apply_replaces_files_and_directories() {
+        let dir = TestDir::new("apply");
+        dir.write("a", "file");
+        dir.write("b/index.html", "dir");
+        dir.write("c/d/e.html", "nested");
+
+        let plan = SyncPlan::new(
+            &dir.0,
+            files(&[("a/index.html", "dir"), ("b", "file")]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            actions(&plan),
+            vec![
+                ("a", SyncAction::Delete),
+                ("a/index.html", SyncAction::Add),
+                ("b", SyncAction::Add),
+                ("b/index.html", SyncAction::Delete),
+                ("c/d/e.html", SyncAction::Delete),
+            ]
+        );
+        plan.apply().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.0.join("a/index.html")).unwrap(),
+            "dir"
+        );
+        assert_eq!(fs::read_to_string(dir.0.join("b")).unwrap(), "file");
+        assert!(!dir.0.join("c").exists());
+        assert!(
+            SyncPlan::new(
+                &dir.0,
+                files(&[("a/index.html", "dir"), ("b", "file")]),
+                false
+            )
+            .unwrap()
+            .actions
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn plan_hidden_files() {
+        let dir = TestDir::new("hidden");
+        dir.write(".nojekyll", "");
+        dir.write(".git/HEAD", "ref");
+        dir.write("sub/.keep", "");
+        dir.write("gone.html", "gone");
+
+        let plan = SyncPlan::new(&dir.0, files(&[]), false).unwrap();
+        assert_eq!(actions(&plan), vec![("gone.html", SyncAction::Delete)]);
+
+        let plan = SyncPlan::new(&dir.0, files(&[]), true).unwrap();
+        assert_eq!(
+            actions(&plan),
+            vec![
+                (".git/HEAD", SyncAction::Delete),
+                (".nojekyll", SyncAction::Delete),
+                ("gone.html", SyncAction::Delete),
+                ("sub/.keep", SyncAction::Delete),
+            ]
+        );
+    }
+}
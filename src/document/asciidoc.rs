@@ -16,16 +16,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+use super::{
+    Diagnostic, external,
+    external::{ExternalOutput, ExternalOutputSpec},
+};
 use crate::Error;
 use serde::{Deserialize, Serialize};
-use std::{path::Path, process::Command};
-use tracing::error;
+use std::{collections::BTreeMap, path::Path, time::Duration};
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AsciiDocOutput {
     pub document: AsciiDocOutputDocument,
-    pub specs: Vec<AsciiDocOutputSpec>,
+    pub specs: Vec<ExternalOutputSpec>,
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -39,35 +44,61 @@ pub struct AsciiDocOutputDocument {
     pub order: Option<usize>,
     pub toc: Option<String>,
     pub created: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    pub publish: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub layout: Option<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
     pub description: String,
     pub content: String,
     pub id: Option<String>,
     pub sitemap_title: Option<String>,
 }
 
-#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AsciiDocOutputSpec {
-    pub id: String,
-    pub description: String,
-    pub discuss: String,
-    pub source_path: String,
-    pub anchor: String,
-}
+impl From<AsciiDocOutput> for ExternalOutput {
+    fn from(output: AsciiDocOutput) -> Self {
+        let document = output.document;
 
-pub fn process_asciidoc(site_path: &Path, rel_path: &Path) -> Result<AsciiDocOutput, Error> {
-    let output = Command::new("eighty-asciidoc")
-        .arg(rel_path)
-        .current_dir(site_path)
-        .output()?;
-
-    if !output.status.success() {
-        match str::from_utf8(&output.stderr) {
-            Ok(stderr) => error!("Asciidoc command failed with stderr: {}", stderr),
-            Err(_) => error!("Asciidoc command failed and cannot decode stderr."),
+        Self {
+            title: document.title,
+            description: document.description,
+            description_content: None,
+            order: document.order,
+            content: document.content,
+            toc: document.toc,
+            id: document.id,
+            sitemap_title: document.sitemap_title,
+            revisions: BTreeMap::new(),
+            license: document.license,
+            license_code: document.license_code,
+            author: document.author,
+            email: document.email,
+            created: document.created,
+            draft: document.draft,
+            publish: document.publish,
+            tags: document.tags,
+            categories: document.categories,
+            layout: document.layout,
+            dependencies: document.dependencies,
+            specs: output.specs,
+            diagnostics: output.diagnostics,
         }
-        return Err(Error::RunCommandFailed);
     }
+}
+
+pub fn process_asciidoc(
+    site_path: &Path,
+    rel_path: &Path,
+    timeout: Duration,
+) -> Result<AsciiDocOutput, Error> {
+    let output = external::run("eighty-asciidoc", &[], site_path, rel_path, timeout)?;
 
-    Ok(serde_json::from_slice(&output.stdout)?)
+    let mut parsed: AsciiDocOutput = serde_json::from_slice(&output.stdout)?;
+    parsed.diagnostics.extend(output.diagnostics);
+    Ok(parsed)
 }
@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{Diagnostic, DiagnosticSeverity};
+use crate::{Error, site::SiteConfigProcessor};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::Path,
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type SharedBuffer = Arc<Mutex<Vec<u8>>>;
+
+/// Output of a processor configured in the `processors` map of
+/// `_site.json`. This is a superset of the outputs of the bundled
+/// processors, which are all converted into it.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalOutput {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub description_content: Option<String>,
+    pub order: Option<usize>,
+    pub content: String,
+    pub toc: Option<String>,
+    pub id: Option<String>,
+    pub sitemap_title: Option<String>,
+    #[serde(default)]
+    pub revisions: BTreeMap<String, String>,
+    pub license: Option<String>,
+    pub license_code: Option<String>,
+    pub author: Option<String>,
+    pub email: Option<String>,
+    pub created: Option<String>,
+    /// Drafts are left out of builds unless drafts are requested.
+    #[serde(default)]
+    pub draft: bool,
+    /// Date before which the document is left out of builds.
+    pub publish: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Layout rendering the document, `document/<layout>`.
+    pub layout: Option<String>,
+    /// Files other than the document read by the processor.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub specs: Vec<ExternalOutputSpec>,
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalOutputSpec {
+    pub id: String,
+    pub description: String,
+    pub discuss: String,
+    pub source_path: String,
+    pub anchor: String,
+}
+
+pub fn process_external(
+    site_path: &Path,
+    rel_path: &Path,
+    processor: &SiteConfigProcessor,
+    timeout: Duration,
+) -> Result<ExternalOutput, Error> {
+    let timeout = processor
+        .timeout
+        .map(Duration::from_secs)
+        .unwrap_or(timeout);
+    let output = run(
+        &processor.command,
+        &processor.args,
+        site_path,
+        rel_path,
+        timeout,
+    )?;
+
+    let mut parsed: ExternalOutput = serde_json::from_slice(&output.stdout)?;
+    parsed.diagnostics.extend(output.diagnostics);
+    Ok(parsed)
+}
+
+pub struct RunOutput {
+    pub stdout: Vec<u8>,
+    /// Diagnostics parsed from the standard error of a successful run.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Run a processor command in the site folder with the document path as the
+/// last argument, killing it if it does not finish within `timeout`.
+pub fn run(
+    command: &str,
+    args: &[String],
+    site_path: &Path,
+    rel_path: &Path,
+    timeout: Duration,
+) -> Result<RunOutput, Error> {
+    let mut command_builder = Command::new(command);
+    command_builder
+        .args(args)
+        .arg(rel_path)
+        .current_dir(site_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Start the processor in its own process group, so that a timeout also
+    // kills everything it spawned.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command_builder, 0);
+    let mut child = command_builder.spawn()?;
+
+    let (stdout_reader, stdout) = read_in_background(child.stdout.take());
+    let (stderr_reader, stderr) = read_in_background(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            kill_process_group(&mut child)?;
+            child.wait()?;
+            break None;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    stdout_reader.join().map_err(|_| Error::Poisoned)??;
+    stderr_reader.join().map_err(|_| Error::Poisoned)??;
+    let stdout = std::mem::take(&mut *stdout.lock()?);
+    let stderr = String::from_utf8_lossy(&stderr.lock()?).into_owned();
+    let path = site_path.join(rel_path);
+
+    match status {
+        None => {
+            error!("Command {} timed out for {:?}", command, path);
+            Err(Error::ProcessorTimeout {
+                path,
+                timeout,
+                stderr,
+            })
+        }
+        Some(status) if !status.success() => {
+            error!(
+                "Command {} failed for {:?} with stderr: {}",
+                command, path, stderr
+            );
+            Err(Error::ProcessorFailed {
+                path,
+                status,
+                stderr,
+            })
+        }
+        Some(_) => Ok(RunOutput {
+            stdout,
+            diagnostics: stderr.lines().filter_map(parse_diagnostic).collect(),
+        }),
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) -> Result<(), Error> {
+    let pgid = libc::pid_t::try_from(child.id()).map_err(std::io::Error::other)?;
+    // SAFETY: killpg has no memory safety requirements; the group was
+    // created by `process_group(0)` above and the child is not reaped yet.
+    if unsafe { libc::killpg(pgid, libc::SIGKILL) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) -> Result<(), Error> {
+    Ok(child.kill()?)
+}
+
+fn read_in_background<R: Read + Send + 'static>(
+    reader: Option<R>,
+) -> (JoinHandle<Result<(), Error>>, SharedBuffer) {
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let thread_buf = buf.clone();
+
+    let handle = thread::spawn(move || {
+        if let Some(mut reader) = reader {
+            let mut chunk = [0; 8192];
+            loop {
+                let read = reader.read(&mut chunk)?;
+                if read == 0 {
+                    break;
+                }
+                thread_buf.lock()?.extend_from_slice(&chunk[..read]);
+            }
+        }
+        Ok(())
+    });
+
+    (handle, buf)
+}
+
+/// Parse a line of processor standard error, such as asciidoctor's
+/// `asciidoctor: WARNING: index.adoc: line 3: section title out of sequence`.
+fn parse_diagnostic(line: &str) -> Option<Diagnostic> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+
+    let mut severity = DiagnosticSeverity::Warning;
+    let mut rest = line;
+    for (prefix, prefix_severity) in [
+        ("ERROR: ", DiagnosticSeverity::Error),
+        ("FATAL: ", DiagnosticSeverity::Error),
+        ("WARNING: ", DiagnosticSeverity::Warning),
+        ("INFO: ", DiagnosticSeverity::Info),
+        ("DEBUG: ", DiagnosticSeverity::Info),
+    ] {
+        if let Some(index) = line.find(prefix) {
+            severity = prefix_severity;
+            rest = &line[index + prefix.len()..];
+            break;
+        }
+    }
+
+    let mut line_number = None;
+    if let Some(index) = rest.find("line ")
+        && let Some((number, message)) = rest[index + "line ".len()..].split_once(": ")
+        && let Ok(number) = number.parse()
+    {
+        line_number = Some(number);
+        rest = message;
+    }
+
+    Some(Diagnostic {
+        severity,
+        message: rest.to_owned(),
+        line: line_number,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(severity: DiagnosticSeverity, message: &str, line: Option<usize>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn parse_asciidoctor_diagnostics() {
+        assert_eq!(
+            parse_diagnostic(
+                "asciidoctor: WARNING: index.adoc: line 3: section title out of sequence"
+            ),
+            Some(diagnostic(
+                DiagnosticSeverity::Warning,
+                "section title out of sequence",
+                Some(3)
+            ))
+        );
+        assert_eq!(
+            parse_diagnostic("asciidoctor: ERROR: index.adoc: line 12: include file not found"),
+            Some(diagnostic(
+                DiagnosticSeverity::Error,
+                "include file not found",
+                Some(12)
+            ))
+        );
+        assert_eq!(
+            parse_diagnostic("FATAL: out of memory"),
+            Some(diagnostic(DiagnosticSeverity::Error, "out of memory", None))
+        );
+        assert_eq!(
+            parse_diagnostic("INFO: done"),
+            Some(diagnostic(DiagnosticSeverity::Info, "done", None))
+        );
+    }
+
+    #[test]
+    fn parse_other_diagnostics() {
+        assert_eq!(
+            parse_diagnostic("  something odd  "),
+            Some(diagnostic(
+                DiagnosticSeverity::Warning,
+                "something odd",
+                None
+            ))
+        );
+        assert_eq!(
+            parse_diagnostic("WARNING: on line two: no number"),
+            Some(diagnostic(
+                DiagnosticSeverity::Warning,
+                "on line two: no number",
+                None
+            ))
+        );
+        assert_eq!(parse_diagnostic(""), None);
+        assert_eq!(parse_diagnostic(" \t "), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout_kills_process_group() {
+        let args = ["-c".to_string(), "sleep 30 & sleep 30".to_string()];
+        let started = Instant::now();
+        let result = run(
+            "sh",
+            &args,
+            &std::env::temp_dir(),
+            Path::new("index.md"),
+            Duration::from_millis(200),
+        );
+
+        assert!(matches!(result, Err(Error::ProcessorTimeout { .. })));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use super::external::ExternalOutput;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonDocOutput {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub description_content: Option<String>,
+    pub order: Option<usize>,
+    pub content: String,
+    pub toc: Option<String>,
+    pub id: Option<String>,
+    pub sitemap_title: Option<String>,
+    #[serde(default)]
+    pub revisions: BTreeMap<String, String>,
+    pub license: Option<String>,
+    pub license_code: Option<String>,
+    pub author: Option<String>,
+    pub email: Option<String>,
+    pub created: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    pub publish: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub layout: Option<String>,
+}
+
+impl From<JsonDocOutput> for ExternalOutput {
+    fn from(output: JsonDocOutput) -> Self {
+        Self {
+            title: output.title,
+            description: output.description,
+            description_content: output.description_content,
+            order: output.order,
+            content: output.content,
+            toc: output.toc,
+            id: output.id,
+            sitemap_title: output.sitemap_title,
+            revisions: output.revisions,
+            license: output.license,
+            license_code: output.license_code,
+            author: output.author,
+            email: output.email,
+            created: output.created,
+            draft: output.draft,
+            publish: output.publish,
+            tags: output.tags,
+            categories: output.categories,
+            layout: output.layout,
+            dependencies: Vec::new(),
+            specs: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+pub fn process_jsondoc(site_path: &Path, rel_path: &Path) -> Result<JsonDocOutput, Error> {
+    let content = fs::read(site_path.join(rel_path))?;
+
+    Ok(serde_json::from_slice(&content)?)
+}
+
+/// Whether a bundled document is an HTML page, that is, whether its file
+/// name ends with `.html.jsondoc`.
+pub fn is_html(rel_path: &Path) -> bool {
+    rel_path
+        .file_stem()
+        .map(|stem| Path::new(stem).extension().and_then(|ext| ext.to_str()) == Some("html"))
+        .unwrap_or(false)
+}
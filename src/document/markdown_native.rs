@@ -0,0 +1,522 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+//! In-process Markdown renderer, producing the same output as the
+//! `eighty-pandoc` processor.
+
+use super::{Diagnostic, DiagnosticSeverity, markdown::MarkdownOutput};
+use crate::{Error, utils};
+use handlebars::html_escape;
+use pulldown_cmark::{CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd, html};
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+/// Maximum heading level included in the table of contents, matching
+/// pandoc's default `--toc-depth`.
+const TOC_DEPTH: HeadingLevel = HeadingLevel::H3;
+
+struct Heading {
+    level: HeadingLevel,
+    id: String,
+    content: String,
+}
+
+pub fn render_markdown(site_path: &Path, rel_path: &Path) -> Result<MarkdownOutput, Error> {
+    let source = fs::read_to_string(site_path.join(rel_path))?;
+
+    let options = Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_SMART_PUNCTUATION
+        | Options::ENABLE_MATH;
+    let events = Parser::new_ext(&source, options).collect::<Vec<_>>();
+
+    let mut metadata = String::new();
+    let mut footnotes = HashMap::<String, Vec<Event>>::new();
+    let mut body = Vec::new();
+
+    let mut iter = events.into_iter();
+    while let Some(event) = iter.next() {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => {
+                for event in iter.by_ref() {
+                    match event {
+                        Event::End(TagEnd::MetadataBlock(_)) => break,
+                        Event::Text(text) => metadata.push_str(&text),
+                        _ => (),
+                    }
+                }
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                let mut depth = 0;
+                let mut definition = Vec::new();
+                for event in iter.by_ref() {
+                    match &event {
+                        Event::Start(Tag::FootnoteDefinition(_)) => depth += 1,
+                        Event::End(TagEnd::FootnoteDefinition) if depth == 0 => break,
+                        Event::End(TagEnd::FootnoteDefinition) => depth -= 1,
+                        _ => (),
+                    }
+                    definition.push(event);
+                }
+                footnotes.insert(label.to_string(), definition);
+            }
+            event => body.push(event),
+        }
+    }
+
+    let front_matter = parse_front_matter(&metadata)?;
+
+    let mut headings = Vec::new();
+    // Explicit `{#id}` identifiers are taken before any is generated, so
+    // that generated ones never duplicate them.
+    let mut used_ids = body
+        .iter()
+        .filter_map(|event| match event {
+            Event::Start(Tag::Heading { id: Some(id), .. }) => Some(id.to_string()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    let mut sidenote_count = 0;
+    let mut diagnostics = Vec::new();
+    let mut output = Vec::with_capacity(body.len());
+
+    let mut iter = body.into_iter();
+    while let Some(event) = iter.next() {
+        match event {
+            Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            }) => {
+                let mut inner = Vec::new();
+                for event in iter.by_ref() {
+                    if let Event::End(TagEnd::Heading(_)) = event {
+                        break;
+                    }
+                    inner.push(event);
+                }
+                inner.retain(|event| !matches!(event, Event::FootnoteReference(_)));
+
+                let id = match id {
+                    Some(id) => id.to_string(),
+                    None => unique_identifier(&plain_text(&inner), &mut used_ids),
+                };
+
+                let mut content = String::new();
+                html::push_html(
+                    &mut content,
+                    inner.iter().filter(|event| !is_link(event)).cloned(),
+                );
+                headings.push(Heading {
+                    level,
+                    id: id.clone(),
+                    content,
+                });
+
+                output.push(Event::Start(Tag::Heading {
+                    level,
+                    id: Some(CowStr::from(id)),
+                    classes,
+                    attrs,
+                }));
+                output.extend(inner);
+                output.push(Event::End(TagEnd::Heading(level)));
+            }
+            Event::FootnoteReference(label) => {
+                let definition = match footnotes.get(label.as_ref()) {
+                    Some(definition) => definition.clone(),
+                    None => {
+                        diagnostics.push(Diagnostic {
+                            severity: DiagnosticSeverity::Warning,
+                            message: format!("footnote [^{}] is not defined", label),
+                            line: None,
+                        });
+                        Vec::new()
+                    }
+                };
+                output.push(Event::InlineHtml(CowStr::from(sidenote(
+                    definition,
+                    sidenote_count,
+                ))));
+                sidenote_count += 1;
+            }
+            Event::InlineMath(math) => {
+                output.push(Event::InlineHtml(CowStr::from(format!(
+                    "<span class=\"math inline\">\\({}\\)</span>",
+                    html_escape(&math)
+                ))));
+            }
+            Event::DisplayMath(math) => {
+                output.push(Event::InlineHtml(CowStr::from(format!(
+                    "<span class=\"math display\">\\[{}\\]</span>",
+                    html_escape(&math)
+                ))));
+            }
+            event => output.push(event),
+        }
+    }
+
+    let dependencies = image_dependencies(site_path, rel_path, &output);
+
+    let mut content = String::new();
+    html::push_html(&mut content, output.into_iter());
+
+    let description = front_matter_scalar(&front_matter, "subtitle")?.unwrap_or_default();
+    let order = front_matter_scalar(&front_matter, "order")?
+        .map(|order| order.parse::<usize>())
+        .transpose()
+        .map_err(|_| Error::InvalidFrontMatter)?;
+    let draft = match front_matter_scalar(&front_matter, "draft")?.as_deref() {
+        None | Some("false") | Some("no") => false,
+        Some("true") | Some("yes") => true,
+        Some(_) => return Err(Error::InvalidFrontMatter),
+    };
+
+    Ok(MarkdownOutput {
+        id: front_matter_scalar(&front_matter, "id")?,
+        title: front_matter_scalar(&front_matter, "title")?.unwrap_or_default(),
+        sitemap_title: front_matter_scalar(&front_matter, "sitemap")?,
+        description_content: description.clone(),
+        description,
+        order,
+        content,
+        toc: toc(&headings),
+        author: front_matter_scalar(&front_matter, "author")?,
+        email: front_matter_scalar(&front_matter, "email")?,
+        created: front_matter_scalar(&front_matter, "created")?,
+        draft,
+        publish: front_matter_scalar(&front_matter, "publish")?,
+        tags: front_matter_list(&front_matter, "tags")?,
+        categories: front_matter_list(&front_matter, "categories")?,
+        layout: front_matter_scalar(&front_matter, "layout")?,
+        diagnostics,
+        dependencies,
+    })
+}
+
+/// Local images referenced by the document, relative to the site folder.
+fn image_dependencies(site_path: &Path, rel_path: &Path, events: &[Event]) -> Vec<String> {
+    let base = rel_path.parent().unwrap_or(Path::new(""));
+
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url),
+            _ => None,
+        })
+        .filter(|dest_url| !dest_url.starts_with('/') && !dest_url.contains(':'))
+        .filter_map(|dest_url| {
+            let target = dest_url.split(['#', '?']).next().unwrap_or_default();
+            let path = utils::normalize_path(&base.join(target));
+            site_path
+                .join(&path)
+                .is_file()
+                .then(|| path.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+/// Parse a YAML metadata block into its top-level mapping.
+fn parse_front_matter(metadata: &str) -> Result<Mapping, Error> {
+    match serde_yaml::from_str::<Value>(metadata)? {
+        Value::Null => Ok(Mapping::new()),
+        Value::Mapping(mapping) => Ok(mapping),
+        _ => Err(Error::InvalidFrontMatter),
+    }
+}
+
+/// A scalar front-matter value as a string, or `None` if it is missing or
+/// empty. Other values, such as lists, are an error.
+fn front_matter_scalar(front_matter: &Mapping, key: &str) -> Result<Option<String>, Error> {
+    match front_matter.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => scalar(value).map(|value| Some(value).filter(|v| !v.is_empty())),
+    }
+}
+
+/// A front-matter list, written either as a YAML sequence or as `a, b`.
+fn front_matter_list(front_matter: &Mapping, key: &str) -> Result<Vec<String>, Error> {
+    match front_matter.get(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Sequence(items)) => items.iter().map(scalar).collect(),
+        Some(value) => Ok(scalar(value)?
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .collect()),
+    }
+}
+
+fn scalar(value: &Value) -> Result<String, Error> {
+    match value {
+        Value::String(s) => Ok(s.trim().to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(Error::InvalidFrontMatter),
+    }
+}
+
+fn is_link(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Start(Tag::Link { .. }) | Event::End(TagEnd::Link)
+    )
+}
+
+fn plain_text(events: &[Event]) -> String {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Text(text) | Event::Code(text) | Event::InlineMath(text) => Some(text.as_ref()),
+            Event::SoftBreak | Event::HardBreak => Some(" "),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Derive a heading identifier the way pandoc's `auto_identifiers` does.
+fn unique_identifier(text: &str, used_ids: &mut HashSet<String>) -> String {
+    let id = text
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.') || c.is_whitespace())
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .flat_map(char::to_lowercase)
+        .skip_while(|c| !c.is_alphabetic())
+        .collect::<String>();
+    let id = if id.is_empty() {
+        "section".to_string()
+    } else {
+        id
+    };
+
+    let mut unique = id.clone();
+    let mut count = 0;
+    while used_ids.contains(&unique) {
+        count += 1;
+        unique = format!("{}-{}", id, count);
+    }
+    used_ids.insert(unique.clone());
+
+    unique
+}
+
+/// Render a footnote as a sidenote, or as a margin note if its content
+/// starts with `{-}`, like `pandoc-sidenote`.
+fn sidenote(definition: Vec<Event>, index: usize) -> String {
+    let mut definition = definition.into_iter().peekable();
+    let mut events = Vec::new();
+    let mut margin = false;
+
+    while let Some(event) = definition.next() {
+        if let Event::Start(Tag::Paragraph) = event {
+            if !events.is_empty() {
+                events.push(Event::InlineHtml(CowStr::from("<br /><br />")));
+            }
+            if events.is_empty()
+                && let Some(Event::Text(text)) = definition.peek()
+                && let Some(rest) = text.strip_prefix("{-}")
+            {
+                margin = true;
+                let rest = rest.trim_start().to_string();
+                definition.next();
+                events.push(Event::Text(CowStr::from(rest)));
+            }
+            continue;
+        }
+        if let Event::End(TagEnd::Paragraph) = event {
+            continue;
+        }
+        events.push(event);
+    }
+
+    let mut content = String::new();
+    html::push_html(&mut content, events.into_iter());
+    let content = content.trim();
+
+    if margin {
+        format!(
+            "<span class=\"sidenote-wrapper\"><label for=\"mn-{index}\" class=\"margin-toggle\">&#8853;</label><input type=\"checkbox\" id=\"mn-{index}\" class=\"margin-toggle\"/><span class=\"marginnote\">{content}</span></span>"
+        )
+    } else {
+        format!(
+            "<span class=\"sidenote-wrapper\"><label for=\"sn-{index}\" class=\"margin-toggle sidenote-number\"></label><input type=\"checkbox\" id=\"sn-{index}\" class=\"margin-toggle\"/><span class=\"sidenote\">{content}</span></span>"
+        )
+    }
+}
+
+/// Render the table of contents as nested lists, like pandoc's `$toc$`.
+fn toc(headings: &[Heading]) -> String {
+    let headings = headings
+        .iter()
+        .filter(|heading| heading.level <= TOC_DEPTH)
+        .collect::<Vec<_>>();
+
+    if headings.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::new();
+    let mut stack = Vec::<HeadingLevel>::new();
+
+    for heading in headings {
+        match stack.last() {
+            None => out.push_str("<ul>\n"),
+            Some(last) if heading.level > *last => out.push_str("\n<ul>\n"),
+            Some(_) => {
+                out.push_str("</li>\n");
+                while stack.len() > 1 && stack[stack.len() - 1] > heading.level {
+                    stack.pop();
+                    out.push_str("</ul></li>\n");
+                }
+                stack.pop();
+            }
+        }
+        stack.push(heading.level);
+
+        out.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            html_escape(&heading.id),
+            heading.content
+        ));
+    }
+
+    out.push_str("</li>\n");
+    while stack.len() > 1 {
+        stack.pop();
+        out.push_str("</ul></li>\n");
+    }
+    out.push_str("</ul>\n");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identifier(text: &str) -> String {
+        unique_identifier(text, &mut HashSet::new())
+    }
+
+    #[test]
+    fn pandoc_identifiers() {
+        // Examples from pandoc's manual.
+        assert_eq!(
+            identifier("Heading identifiers in HTML"),
+            "heading-identifiers-in-html"
+        );
+        assert_eq!(identifier("Maître d'hôtel"), "maître-dhôtel");
+        assert_eq!(identifier("Dogs?--in my house?"), "dogs--in-my-house");
+        assert_eq!(identifier("HTML, S5, or RTF?"), "html-s5-or-rtf");
+        assert_eq!(identifier("3. Applications"), "applications");
+        assert_eq!(identifier("33"), "section");
+    }
+
+    #[test]
+    fn empty_identifiers() {
+        assert_eq!(identifier(""), "section");
+        assert_eq!(identifier("?!"), "section");
+    }
+
+    #[test]
+    fn duplicate_identifiers() {
+        let mut used_ids = HashSet::new();
+        assert_eq!(unique_identifier("Intro", &mut used_ids), "intro");
+        assert_eq!(unique_identifier("Intro", &mut used_ids), "intro-1");
+        assert_eq!(unique_identifier("intro", &mut used_ids), "intro-2");
+        assert_eq!(unique_identifier("", &mut used_ids), "section");
+        assert_eq!(unique_identifier("1", &mut used_ids), "section-1");
+    }
+
+    #[test]
+    fn front_matter_lists() {
+        let front_matter = parse_front_matter(
+            "tags:\n  - Rust\n  - web\ncategories: [a, \"b\"]\nkeywords: x, y\n",
+        )
+        .unwrap();
+        assert_eq!(
+            front_matter_list(&front_matter, "tags").unwrap(),
+            vec!["Rust", "web"]
+        );
+        assert_eq!(
+            front_matter_list(&front_matter, "categories").unwrap(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            front_matter_list(&front_matter, "keywords").unwrap(),
+            vec!["x", "y"]
+        );
+        assert!(
+            front_matter_list(&front_matter, "missing")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn front_matter_scalars() {
+        let front_matter = parse_front_matter(
+            "title: \"A: B\"\norder: 3\ndraft: true\ncreated: 2024-01-02\nempty:\n",
+        )
+        .unwrap();
+        let scalar = |key| front_matter_scalar(&front_matter, key).unwrap();
+        assert_eq!(scalar("title").as_deref(), Some("A: B"));
+        assert_eq!(scalar("order").as_deref(), Some("3"));
+        assert_eq!(scalar("draft").as_deref(), Some("true"));
+        assert_eq!(scalar("created").as_deref(), Some("2024-01-02"));
+        assert_eq!(scalar("empty"), None);
+        assert_eq!(scalar("missing"), None);
+    }
+
+    #[test]
+    fn invalid_front_matter() {
+        assert!(parse_front_matter("- a\n- b\n").is_err());
+        assert!(parse_front_matter("title: [unclosed\n").is_err());
+
+        let front_matter = parse_front_matter("title:\n  nested: true\ntags:\n  - a: b\n").unwrap();
+        assert!(front_matter_scalar(&front_matter, "title").is_err());
+        assert!(front_matter_list(&front_matter, "tags").is_err());
+        assert!(parse_front_matter("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn explicit_identifiers_are_reserved() {
+        let dir = std::env::temp_dir().join(format!("eighty-markdown-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("index.md"),
+            "---\ntitle: T\ntags:\n  - a\n---\n\n# Intro\n\n# Other {#intro}\n",
+        )
+        .unwrap();
+
+        let output = render_markdown(&dir, Path::new("index.md"));
+        let _ = fs::remove_dir_all(&dir);
+        let output = output.unwrap();
+
+        assert_eq!(output.tags, vec!["a"]);
+        assert!(output.content.contains("<h1 id=\"intro-1\">Intro</h1>"));
+        assert!(output.content.contains("<h1 id=\"intro\">Other</h1>"));
+    }
+}
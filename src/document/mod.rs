@@ -17,25 +17,38 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 mod asciidoc;
+mod external;
+mod jsondoc;
 mod markdown;
+mod markdown_native;
 mod org;
 
-use crate::{Error, site::SiteMetadata};
+use self::external::ExternalOutput;
+use crate::{
+    Error,
+    site::{SiteConfigMarkdown, SiteMetadata},
+    utils,
+};
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
     fmt,
     path::{Component, Path, PathBuf},
     sync::Arc,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
-use tracing::info;
+use tracing::{error, info, warn};
 
-#[derive(Hash, Eq, Clone, PartialEq, Debug)]
+#[derive(Hash, Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DocumentName {
     pub id: Option<String>,
     pub labels: Vec<String>,
     pub post: Option<DocumentPostLabel>,
+    /// Whether the document is emitted verbatim at its folder path, instead
+    /// of as an `index.html` page.
+    pub raw: bool,
 }
 
 impl fmt::Display for DocumentName {
@@ -57,7 +70,9 @@ impl DocumentName {
     pub fn path(&self) -> PathBuf {
         let mut path = self.folder_path();
 
-        path.push("index.html");
+        if !self.raw {
+            path.push("index.html");
+        }
 
         path
     }
@@ -102,7 +117,7 @@ impl DocumentName {
             return self.post.is_none() && child.post.is_some();
         }
 
-        return true;
+        true
     }
 
     pub fn is_root(&self) -> bool {
@@ -110,7 +125,8 @@ impl DocumentName {
     }
 }
 
-#[derive(Hash, Eq, Clone, PartialEq, Debug, PartialOrd, Ord)]
+#[derive(Hash, Eq, Clone, PartialEq, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DocumentPostLabel {
     pub date: String,
     pub label: String,
@@ -121,6 +137,22 @@ pub enum DocumentType {
     AsciiDoc,
     Markdown,
     Org,
+    JsonDoc,
+    /// Document rendered by a processor configured for the given file
+    /// extension in `_site.json`.
+    External(String),
+}
+
+impl DocumentType {
+    /// Find the document type of a file extension, with the processors
+    /// configured for the site taking precedence over the bundled ones.
+    pub fn from_extension(site: &SiteMetadata, extension: &str) -> Option<DocumentType> {
+        if site.config.processors.contains_key(extension) {
+            Some(DocumentType::External(extension.to_owned()))
+        } else {
+            DocumentType::try_from(extension).ok()
+        }
+    }
 }
 
 impl<'a> TryFrom<&'a str> for DocumentType {
@@ -131,6 +163,7 @@ impl<'a> TryFrom<&'a str> for DocumentType {
             "adoc" => Ok(DocumentType::AsciiDoc),
             "md" => Ok(DocumentType::Markdown),
             "org" => Ok(DocumentType::Org),
+            "jsondoc" => Ok(DocumentType::JsonDoc),
             _ => Err(()),
         }
     }
@@ -206,10 +239,58 @@ fn derive_name(rel_file_path: &Path, id: Option<String>) -> Result<DocumentName,
         None
     };
 
-    Ok(DocumentName { id, labels, post })
+    Ok(DocumentName {
+        id,
+        labels,
+        post,
+        raw: false,
+    })
 }
 
-#[derive(Eq, Clone, PartialEq, Debug)]
+fn parse_date(date: Option<String>) -> Result<Option<NaiveDate>, Error> {
+    date.map(|date| {
+        NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .or_else(|_| NaiveDate::parse_from_str(&date, "%Y%m%d"))
+    })
+    .transpose()
+    .map_err(Into::into)
+}
+
+/// Trimmed tags or categories, without empty or duplicate ones.
+fn parse_terms(terms: Vec<String>) -> Vec<String> {
+    let mut parsed = Vec::<String>::new();
+    for term in terms {
+        let term = term.trim();
+        if !term.is_empty() && !parsed.iter().any(|v| v == term) {
+            parsed.push(term.to_string());
+        }
+    }
+    parsed
+}
+
+fn parse_dependencies(dependencies: Vec<String>) -> Vec<PathBuf> {
+    dependencies
+        .into_iter()
+        .map(|dependency| utils::normalize_path(Path::new(&dependency)))
+        .collect()
+}
+
+fn parse_revisions(
+    revisions: BTreeMap<String, String>,
+) -> Result<BTreeMap<NaiveDate, RevisionData>, Error> {
+    revisions
+        .into_iter()
+        .map(|(k, v)| {
+            Ok((
+                NaiveDate::parse_from_str(&k, "%Y%m%d")?,
+                RevisionData::new(v),
+            ))
+        })
+        .collect()
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum RevisionData {
     Created,
     Custom(String),
@@ -234,7 +315,25 @@ impl fmt::Display for RevisionData {
     }
 }
 
-#[derive(Eq, Clone, PartialEq, Debug)]
+#[derive(Eq, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A non-fatal message reported by a processor while rendering a document.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    pub line: Option<usize>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RenderedData {
     pub name: DocumentName,
     pub title: String,
@@ -248,6 +347,36 @@ pub struct RenderedData {
     pub specs: Vec<Spec>,
     pub order: Option<usize>,
     pub revisions: BTreeMap<NaiveDate, RevisionData>,
+    pub author: Option<String>,
+    pub email: Option<String>,
+    pub created: Option<NaiveDate>,
+    pub draft: bool,
+    pub publish: Option<NaiveDate>,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+    pub layout: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Files other than the document read while rendering it, relative to
+    /// the site folder.
+    pub dependencies: Vec<PathBuf>,
+}
+
+impl RenderedData {
+    /// Date from which the document is published: its `publish` attribute,
+    /// or else the date of a post.
+    pub fn publish_date(&self) -> Option<NaiveDate> {
+        self.publish.or_else(|| {
+            self.name
+                .post
+                .as_ref()
+                .and_then(|post| NaiveDate::parse_from_str(&post.date, "%Y-%m-%d").ok())
+        })
+    }
+
+    /// Whether the document is neither a draft nor scheduled after `today`.
+    pub fn is_published(&self, today: NaiveDate) -> bool {
+        !self.draft && self.publish_date().is_none_or(|date| date <= today)
+    }
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
@@ -266,104 +395,137 @@ impl RenderedDocument {
 
         info!("[{}] Rendering document {:?} ...", site.name, rel_file_path);
 
-        Ok(match document.typ {
+        let timeout = Duration::from_secs(site.config.processor_timeout);
+
+        let output: ExternalOutput = match &document.typ {
             DocumentType::AsciiDoc => {
-                let output = self::asciidoc::process_asciidoc(&site.source_path, &rel_file_path)?;
-                let id = output.document.id.clone();
-                let name = derive_name(rel_file_path, id)?;
-
-                RenderedDocument {
-                    site_metadata: site,
-                    metadata: document,
-                    data: Arc::new(RenderedData {
-                        name,
-                        title: output.document.title,
-                        sitemap_title: output.document.sitemap_title,
-                        content: output.document.content,
-                        toc: output.document.toc,
-                        description: output.document.description.clone(),
-                        description_content: None,
-                        license: output.document.license,
-                        license_code: output.document.license_code,
-                        order: output.document.order,
-                        specs: output
-                            .specs
-                            .into_iter()
-                            .map(|spec| Spec {
-                                id: spec.id,
-                                description: spec.description,
-                                discuss: spec.discuss,
-                                source_path: Path::new(&spec.source_path).to_owned(),
-                                anchor: spec.anchor,
-                            })
-                            .collect(),
-                        revisions: BTreeMap::new(),
-                    }),
-                }
+                self::asciidoc::process_asciidoc(&site.source_path, rel_file_path, timeout)?.into()
             }
-            DocumentType::Markdown => {
-                let output = self::markdown::process_markdown(&site.source_path, &rel_file_path)?;
-                let id = output.id;
-                let name = derive_name(rel_file_path, id)?;
-
-                RenderedDocument {
-                    site_metadata: site,
-                    metadata: document,
-                    data: Arc::new(RenderedData {
-                        name,
-                        title: output.title,
-                        sitemap_title: output.sitemap_title,
-                        content: output.content,
-                        toc: Some(output.toc),
-                        description: output.description,
-                        description_content: Some(output.description_content),
-                        license: None,
-                        license_code: None,
-                        specs: Vec::new(),
-                        order: output.order,
-                        revisions: BTreeMap::new(),
-                    }),
+            DocumentType::Markdown => match site.config.markdown {
+                SiteConfigMarkdown::Pandoc => {
+                    self::markdown::process_markdown(&site.source_path, rel_file_path, timeout)?
+                        .into()
                 }
-            }
+                SiteConfigMarkdown::Native => {
+                    self::markdown_native::render_markdown(&site.source_path, rel_file_path)?.into()
+                }
+            },
             DocumentType::Org => {
-                let output = self::org::process_org(&site.source_path, &rel_file_path)?;
-                let id = output.id.clone();
-                let name = derive_name(rel_file_path, id)?;
-                let revisions = output
-                    .revisions
+                self::org::process_org(&site.source_path, rel_file_path, timeout)?.into()
+            }
+            DocumentType::JsonDoc => {
+                self::jsondoc::process_jsondoc(&site.source_path, rel_file_path)?.into()
+            }
+            DocumentType::External(extension) => {
+                let processor = site
+                    .config
+                    .processors
+                    .get(extension)
+                    .ok_or(Error::UnknownCommand)?;
+                self::external::process_external(
+                    &site.source_path,
+                    rel_file_path,
+                    processor,
+                    timeout,
+                )?
+            }
+        };
+
+        // Bundled documents are named after their file name without the
+        // `.jsondoc` extension, and are emitted verbatim unless they are
+        // HTML pages.
+        let id = output.id.clone();
+        let name = match &document.typ {
+            DocumentType::JsonDoc if self::jsondoc::is_html(rel_file_path) => {
+                derive_name(&rel_file_path.with_extension(""), id)?
+            }
+            DocumentType::JsonDoc => DocumentName {
+                raw: true,
+                ..derive_name(rel_file_path, id)?
+            },
+            _ => derive_name(rel_file_path, id)?,
+        };
+
+        let rendered = RenderedDocument {
+            site_metadata: site,
+            metadata: document,
+            data: Arc::new(RenderedData {
+                name,
+                title: output.title,
+                sitemap_title: output.sitemap_title,
+                content: output.content,
+                toc: output.toc,
+                description: output.description,
+                description_content: output.description_content,
+                license: output.license,
+                license_code: output.license_code,
+                specs: output
+                    .specs
                     .into_iter()
-                    .map(|(k, v)| {
-                        Ok((
-                            NaiveDate::parse_from_str(&k, "%Y%m%d")?,
-                            RevisionData::new(v),
-                        ))
+                    .map(|spec| Spec {
+                        id: spec.id,
+                        description: spec.description,
+                        discuss: spec.discuss,
+                        source_path: Path::new(&spec.source_path).to_owned(),
+                        anchor: spec.anchor,
                     })
-                    .collect::<Result<_, Error>>()?;
-
-                RenderedDocument {
-                    site_metadata: site,
-                    metadata: document,
-                    data: Arc::new(RenderedData {
-                        name,
-                        title: output.title,
-                        sitemap_title: output.sitemap_title,
-                        content: output.content,
-                        toc: Some(output.toc),
-                        description: output.description,
-                        description_content: Some(output.description_content),
-                        license: output.license,
-                        license_code: output.license_code,
-                        specs: Vec::new(),
-                        order: output.order,
-                        revisions,
-                    }),
-                }
+                    .collect(),
+                order: output.order,
+                revisions: parse_revisions(output.revisions)?,
+                author: output.author,
+                email: output.email,
+                created: parse_date(output.created)?,
+                draft: output.draft,
+                publish: parse_date(output.publish)?,
+                tags: parse_terms(output.tags),
+                categories: parse_terms(output.categories),
+                layout: output.layout,
+                dependencies: parse_dependencies(output.dependencies),
+                diagnostics: output.diagnostics,
+            }),
+        };
+
+        rendered.log_diagnostics();
+
+        Ok(rendered)
+    }
+
+    /// Log the diagnostics reported by the processor of the document.
+    pub fn log_diagnostics(&self) {
+        for diagnostic in &self.data.diagnostics {
+            let line = diagnostic
+                .line
+                .map(|line| format!(":{}", line))
+                .unwrap_or_default();
+            match diagnostic.severity {
+                DiagnosticSeverity::Error => error!(
+                    "[{}] {}{}: {}",
+                    self.site_metadata.name,
+                    self.metadata.rel_source_path.display(),
+                    line,
+                    diagnostic.message
+                ),
+                DiagnosticSeverity::Warning => warn!(
+                    "[{}] {}{}: {}",
+                    self.site_metadata.name,
+                    self.metadata.rel_source_path.display(),
+                    line,
+                    diagnostic.message
+                ),
+                DiagnosticSeverity::Info => info!(
+                    "[{}] {}{}: {}",
+                    self.site_metadata.name,
+                    self.metadata.rel_source_path.display(),
+                    line,
+                    diagnostic.message
+                ),
             }
-        })
+        }
     }
 }
 
-#[derive(Eq, Clone, PartialEq, Debug)]
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Spec {
     pub id: String,
     pub description: String,
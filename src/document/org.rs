@@ -16,9 +16,10 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+use super::{Diagnostic, external, external::ExternalOutput};
 use crate::Error;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, path::Path, process::Command};
+use std::{collections::BTreeMap, path::Path, time::Duration};
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -34,17 +35,60 @@ pub struct OrgOutput {
     pub revisions: BTreeMap<String, String>,
     pub license: Option<String>,
     pub license_code: Option<String>,
+    pub author: Option<String>,
+    pub email: Option<String>,
+    pub created: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    pub publish: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub layout: Option<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
-pub fn process_org(site_path: &Path, rel_path: &Path) -> Result<OrgOutput, Error> {
-    let output = Command::new("eighty-pandoc")
-        .arg(rel_path)
-        .current_dir(site_path)
-        .output()?;
-
-    if !output.status.success() {
-        return Err(Error::RunCommandFailed);
+impl From<OrgOutput> for ExternalOutput {
+    fn from(output: OrgOutput) -> Self {
+        Self {
+            title: output.title,
+            description: output.description,
+            description_content: Some(output.description_content),
+            order: output.order,
+            content: output.content,
+            toc: Some(output.toc),
+            id: output.id,
+            sitemap_title: output.sitemap_title,
+            revisions: output.revisions,
+            license: output.license,
+            license_code: output.license_code,
+            author: output.author,
+            email: output.email,
+            created: output.created,
+            draft: output.draft,
+            publish: output.publish,
+            tags: output.tags,
+            categories: output.categories,
+            layout: output.layout,
+            dependencies: output.dependencies,
+            specs: Vec::new(),
+            diagnostics: output.diagnostics,
+        }
     }
+}
+
+pub fn process_org(
+    site_path: &Path,
+    rel_path: &Path,
+    timeout: Duration,
+) -> Result<OrgOutput, Error> {
+    let output = external::run("eighty-pandoc", &[], site_path, rel_path, timeout)?;
 
-    Ok(serde_json::from_slice(&output.stdout)?)
+    let mut parsed: OrgOutput = serde_json::from_slice(&output.stdout)?;
+    parsed.diagnostics.extend(output.diagnostics);
+    Ok(parsed)
 }
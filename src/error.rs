@@ -17,7 +17,12 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 use snafu::Snafu;
-use std::backtrace::Backtrace;
+use std::{
+    backtrace::Backtrace,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::Duration,
+};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -36,6 +41,10 @@ pub enum Error {
         source: serde_json::Error,
     },
     #[snafu(context(false))]
+    Yaml {
+        source: serde_yaml::Error,
+    },
+    #[snafu(context(false))]
     StripPrefix {
         source: std::path::StripPrefixError,
     },
@@ -56,6 +65,10 @@ pub enum Error {
         source: hyper::http::Error,
     },
     #[snafu(context(false))]
+    Hyper {
+        source: hyper::Error,
+    },
+    #[snafu(context(false))]
     HandlebarsTemplate {
         source: handlebars::TemplateError,
     },
@@ -76,6 +89,10 @@ pub enum Error {
     UnprocessedRegexMatch,
     UnsupportedVariable,
     UnresolvedXreflink,
+    DuplicateSpecId,
+    InvalidSpecId,
+    PageConflict,
+    InvalidFrontMatter,
 
     #[snafu(context(false))]
     ChronoParse {
@@ -88,6 +105,43 @@ pub enum Error {
 
     PathDiffFailed,
     MalformedLink,
+    BrokenLinks,
+    ManifestConflict,
+    #[snafu(display("build target {} contains the source folder {}", target.display(), source_path.display()))]
+    TargetContainsSource {
+        target: PathBuf,
+        source_path: PathBuf,
+    },
+    #[snafu(display("processor for {} failed with {}: {}", path.display(), status, stderr))]
+    ProcessorFailed {
+        path: PathBuf,
+        status: ExitStatus,
+        stderr: String,
+    },
+    #[snafu(display("{}: {}", path.display(), source))]
+    Document {
+        path: PathBuf,
+        source: Box<Error>,
+    },
+    #[snafu(display("processor for {} timed out after {:?}: {}", path.display(), timeout, stderr))]
+    ProcessorTimeout {
+        path: PathBuf,
+        timeout: Duration,
+        stderr: String,
+    },
+}
+
+impl Error {
+    /// Attribute an error to the document at `path`, relative to its site.
+    pub fn in_document(self, path: &Path) -> Error {
+        match self {
+            Error::Document { .. } => self,
+            source => Error::Document {
+                path: path.to_owned(),
+                source: Box::new(source),
+            },
+        }
+    }
 }
 
 impl<T> From<std::sync::PoisonError<T>> for Error {
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    Error,
+    utils::{self, xml_escape},
+    workspace::{FullDocument, FullSite},
+};
+use chrono::NaiveDate;
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+pub const ATOM_FILE_NAME: &str = "atom.xml";
+pub const RSS_FILE_NAME: &str = "rss.xml";
+
+struct FeedEntry<'a> {
+    document: &'a FullDocument,
+    date: NaiveDate,
+    url: String,
+}
+
+struct Feed<'a> {
+    title: String,
+    url: String,
+    folder_path: PathBuf,
+    entries: Vec<&'a FeedEntry<'a>>,
+}
+
+/// Generate Atom, and optionally RSS, feeds of posts for the whole site and
+/// for every labelled folder that has posts.
+pub fn files(site: &FullSite) -> Result<HashMap<PathBuf, Vec<u8>>, Error> {
+    let config = &site.site.config;
+    let mut files = HashMap::new();
+
+    if !config.feed.enable {
+        return Ok(files);
+    }
+
+    let mut entries = site
+        .documents
+        .iter()
+        .filter_map(|document| {
+            document
+                .rendered
+                .name
+                .post
+                .as_ref()
+                .map(|post| (document, post))
+        })
+        .map(|(document, post)| {
+            Ok(FeedEntry {
+                document,
+                date: NaiveDate::parse_from_str(&post.date, "%Y-%m-%d")?,
+                url: utils::folder_url(&config.url, &document.rendered.name.folder_path()),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    entries.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.url.cmp(&b.url)));
+
+    if entries.is_empty() {
+        return Ok(files);
+    }
+
+    let mut folders = BTreeMap::<Vec<String>, Vec<&FeedEntry>>::new();
+    folders.insert(Vec::new(), entries.iter().collect());
+    for entry in &entries {
+        let labels = &entry.document.rendered.name.labels;
+        if !labels.is_empty() {
+            folders.entry(labels.clone()).or_default().push(entry);
+        }
+    }
+
+    for (labels, mut folder_entries) in folders {
+        if let Some(limit) = config.feed.limit {
+            folder_entries.truncate(limit);
+        }
+
+        let folder_path = labels.iter().collect::<PathBuf>();
+        let title = if labels.is_empty() {
+            config.title.clone()
+        } else {
+            site.documents
+                .iter()
+                .find(|document| {
+                    let name = &document.rendered.name;
+                    name.post.is_none() && !name.raw && name.labels == labels
+                })
+                .map(|document| format!("{}: {}", config.title, document.rendered.title))
+                .unwrap_or(format!("{}: {}", config.title, labels.join("/")))
+        };
+
+        let feed = Feed {
+            title,
+            url: utils::folder_url(&config.url, &folder_path),
+            folder_path,
+            entries: folder_entries,
+        };
+
+        files.insert(
+            feed.folder_path.join(ATOM_FILE_NAME),
+            atom(site, &feed).into_bytes(),
+        );
+        if config.feed.rss {
+            files.insert(
+                feed.folder_path.join(RSS_FILE_NAME),
+                rss(site, &feed)?.into_bytes(),
+            );
+        }
+    }
+
+    Ok(files)
+}
+
+fn feed_file_url(site: &FullSite, feed: &Feed, file_name: &str) -> String {
+    format!(
+        "{}{}",
+        site.site.config.url,
+        feed.folder_path.join(file_name).display()
+    )
+}
+
+fn atom(site: &FullSite, feed: &Feed) -> String {
+    let config = &site.site.config;
+    let updated = feed
+        .entries
+        .iter()
+        .map(|entry| entry.date)
+        .max()
+        .map(|date| format_atom_date(&date))
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    out.push_str(&format!("  <title>{}</title>\n", xml_escape(&feed.title)));
+    out.push_str(&format!("  <link href=\"{}\"/>\n", xml_escape(&feed.url)));
+    out.push_str(&format!(
+        "  <link rel=\"self\" href=\"{}\"/>\n",
+        xml_escape(&feed_file_url(site, feed, ATOM_FILE_NAME))
+    ));
+    out.push_str(&format!("  <id>{}</id>\n", xml_escape(&feed.url)));
+    out.push_str(&format!("  <updated>{}</updated>\n", updated));
+    out.push_str(&format!(
+        "  <author><name>{}</name><email>{}</email></author>\n",
+        xml_escape(&config.author),
+        xml_escape(&config.email)
+    ));
+
+    for entry in &feed.entries {
+        let rendered = &entry.document.rendered;
+        let date = format_atom_date(&entry.date);
+
+        out.push_str("  <entry>\n");
+        out.push_str(&format!(
+            "    <title>{}</title>\n",
+            xml_escape(&rendered.title)
+        ));
+        out.push_str(&format!(
+            "    <link href=\"{}\"/>\n",
+            xml_escape(&entry.url)
+        ));
+        out.push_str(&format!("    <id>{}</id>\n", xml_escape(&entry.url)));
+        out.push_str(&format!("    <published>{}</published>\n", date));
+        out.push_str(&format!("    <updated>{}</updated>\n", date));
+        if let Some(author) = &rendered.author {
+            out.push_str(&format!(
+                "    <author><name>{}</name></author>\n",
+                xml_escape(author)
+            ));
+        }
+        out.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            xml_escape(&rendered.description)
+        ));
+        out.push_str(&format!(
+            "    <content type=\"html\" xml:base=\"{}\">{}</content>\n",
+            xml_escape(&entry.url),
+            xml_escape(&entry.document.body)
+        ));
+        out.push_str("  </entry>\n");
+    }
+
+    out.push_str("</feed>\n");
+    out
+}
+
+fn rss(site: &FullSite, feed: &Feed) -> Result<String, Error> {
+    let config = &site.site.config;
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+    out.push_str("  <channel>\n");
+    out.push_str(&format!("    <title>{}</title>\n", xml_escape(&feed.title)));
+    out.push_str(&format!("    <link>{}</link>\n", xml_escape(&feed.url)));
+    out.push_str(&format!(
+        "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        xml_escape(&feed_file_url(site, feed, RSS_FILE_NAME))
+    ));
+    out.push_str(&format!(
+        "    <description>{}</description>\n",
+        xml_escape(&config.title)
+    ));
+    if let Some(entry) = feed.entries.first() {
+        out.push_str(&format!(
+            "    <lastBuildDate>{}</lastBuildDate>\n",
+            format_rss_date(&entry.date)
+        ));
+    }
+
+    for entry in &feed.entries {
+        let rendered = &entry.document.rendered;
+
+        out.push_str("    <item>\n");
+        out.push_str(&format!(
+            "      <title>{}</title>\n",
+            xml_escape(&rendered.title)
+        ));
+        out.push_str(&format!("      <link>{}</link>\n", xml_escape(&entry.url)));
+        out.push_str(&format!(
+            "      <guid isPermaLink=\"true\">{}</guid>\n",
+            xml_escape(&entry.url)
+        ));
+        out.push_str(&format!(
+            "      <pubDate>{}</pubDate>\n",
+            format_rss_date(&entry.date)
+        ));
+        // RSS has no equivalent of Atom's `xml:base`, so links in the
+        // description must be absolute.
+        out.push_str(&format!(
+            "      <description>{}</description>\n",
+            xml_escape(&absolute_links(&entry.document.body, &entry.url)?)
+        ));
+        out.push_str("    </item>\n");
+    }
+
+    out.push_str("  </channel>\n");
+    out.push_str("</rss>\n");
+    Ok(out)
+}
+
+/// Rewrite the relative `href` and `src` attributes of `content` into
+/// absolute URLs, relative to the folder URL `base`.
+fn absolute_links(content: &str, base: &str) -> Result<String, Error> {
+    Ok(rewrite_str(
+        content,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("[href], [src]", |el| {
+                for attribute in ["href", "src"] {
+                    if let Some(link) = el.get_attribute(attribute) {
+                        el.set_attribute(attribute, &absolute_url(base, &link))?;
+                    }
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?)
+}
+
+fn absolute_url(base: &str, link: &str) -> String {
+    let has_scheme = link
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains(['/', '#', '?']));
+    if has_scheme || link.starts_with("//") {
+        return link.to_owned();
+    }
+
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return link.to_owned();
+    };
+    let (host, base_path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+
+    let suffix_start = link.find(['?', '#']).unwrap_or(link.len());
+    let (link_path, suffix) = link.split_at(suffix_start);
+
+    let path = if link_path.is_empty() {
+        base_path.to_owned()
+    } else {
+        let path = utils::normalize_path(&Path::new(base_path).join(link_path));
+        let mut path = path.display().to_string();
+        if link_path.ends_with('/') && !path.ends_with('/') {
+            path.push('/');
+        }
+        path
+    };
+
+    format!("{}://{}{}{}", scheme, host, path, suffix)
+}
+
+fn format_atom_date(date: &NaiveDate) -> String {
+    date.format("%Y-%m-%dT00:00:00Z").to_string()
+}
+
+fn format_rss_date(date: &NaiveDate) -> String {
+    date.format("%a, %d %b %Y 00:00:00 +0000").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "https://example.org/news/2024/01/02/first/";
+
+    #[test]
+    fn absolute_urls() {
+        assert_eq!(
+            absolute_url(BASE, "image.png"),
+            "https://example.org/news/2024/01/02/first/image.png"
+        );
+        assert_eq!(
+            absolute_url(BASE, "../../../03/05/second/#top"),
+            "https://example.org/news/2024/03/05/second/#top"
+        );
+        assert_eq!(
+            absolute_url(BASE, "/css/main.css?v=1"),
+            "https://example.org/css/main.css?v=1"
+        );
+        assert_eq!(absolute_url(BASE, "#section"), format!("{}#section", BASE));
+        assert_eq!(
+            absolute_url(BASE, "../../../../../../../x"),
+            "https://example.org/x"
+        );
+    }
+
+    #[test]
+    fn absolute_urls_unchanged() {
+        for link in [
+            "https://other.org/a",
+            "//cdn.example.org/a.js",
+            "mailto:a@example.org",
+            "data:image/png;base64,AAAA",
+        ] {
+            assert_eq!(absolute_url(BASE, link), link);
+        }
+    }
+
+    #[test]
+    fn absolute_links_in_content() {
+        assert_eq!(
+            absolute_links("<a href=\"../x/\">x</a><img src=\"a.png\">", BASE).unwrap(),
+            "<a href=\"https://example.org/news/2024/01/02/x/\">x</a>\
+             <img src=\"https://example.org/news/2024/01/02/first/a.png\">"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    Error,
+    archive::{ARCHIVE_TEMPLATE, ArchivePage},
+    site::{SiteConfig, SiteMetadata},
+    utils,
+};
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveContext {
+    pub site_title: String,
+    pub site_base_url: String,
+    pub site_links: Vec<ArchiveContextSiteLink>,
+    pub page_url: String,
+    pub archive: ArchiveContextArchive,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveContextSiteLink {
+    pub url: String,
+    pub name: String,
+}
+
+/// A page of a post archive, also exposed to the layout of the document of
+/// a folder with posts.
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ArchiveContextArchive {
+    pub url: String,
+    pub year: Option<String>,
+    pub month: Option<String>,
+    pub page: usize,
+    pub page_count: usize,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+    pub posts: Vec<ArchiveContextPost>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ArchiveContextPost {
+    pub title: String,
+    pub url: String,
+    pub description: String,
+    pub date: String,
+}
+
+pub(super) fn folder_url(config: &SiteConfig, folder_path: &Path) -> String {
+    utils::folder_url(&config.base_url, folder_path)
+}
+
+pub(super) fn archive_context(config: &SiteConfig, page: &ArchivePage) -> ArchiveContextArchive {
+    ArchiveContextArchive {
+        url: folder_url(config, &page.archive_path),
+        year: page.year.clone(),
+        month: page.month.clone(),
+        page: page.page,
+        page_count: page.page_count,
+        prev_url: page
+            .prev_folder_path()
+            .map(|path| folder_url(config, &path)),
+        next_url: page
+            .next_folder_path()
+            .map(|path| folder_url(config, &path)),
+        posts: page
+            .posts
+            .iter()
+            .map(|post| ArchiveContextPost {
+                title: post.data.title.clone(),
+                url: folder_url(config, &post.data.name.folder_path()),
+                description: post.data.description.clone(),
+                date: post
+                    .data
+                    .name
+                    .post
+                    .as_ref()
+                    .map(|post| post.date.clone())
+                    .unwrap_or_default(),
+            })
+            .collect(),
+    }
+}
+
+/// Render the `archive` layout for a generated page of a post archive.
+pub fn layout(
+    site: &SiteMetadata,
+    handlebars: &Handlebars,
+    page: &ArchivePage,
+) -> Result<String, Error> {
+    let config = &site.config;
+
+    let context = ArchiveContext {
+        site_title: config.title.clone(),
+        site_base_url: config.base_url.clone(),
+        site_links: config
+            .links
+            .iter()
+            .map(|link| ArchiveContextSiteLink {
+                url: link.url.clone(),
+                name: link.name.clone(),
+            })
+            .collect(),
+        page_url: format!("{}{}", config.url, page.folder_path().display()),
+        archive: archive_context(config, page),
+    };
+
+    Ok(handlebars.render(ARCHIVE_TEMPLATE, &context)?)
+}
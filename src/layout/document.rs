@@ -16,13 +16,23 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+use super::archive::{ArchiveContextArchive, archive_context};
 use crate::{
     Error,
+    archive::ArchivePage,
     document::RenderedDocument,
-    sitemap::{LocalSitemap, Sitemap, SitemapItem},
+    feed, search,
+    sitemap::{BreadcrumbItem, LocalSitemap, Sitemap, SitemapItem},
+    taxonomy::{TAXONOMY_TERM_TEMPLATE, Taxonomy},
+    utils,
 };
+use chrono::Datelike;
 use handlebars::Handlebars;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::warn;
+
+const DEFAULT_TEMPLATE: &str = "document/main";
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -30,6 +40,8 @@ struct DocumentContext {
     pub site_title: String,
     pub site_title_only: bool,
     pub site_base_url: String,
+    pub site_feed_url: Option<String>,
+    pub site_search: Option<DocumentContextSearch>,
 
     pub page_title: String,
     pub page_description: Option<String>,
@@ -44,15 +56,29 @@ struct DocumentContext {
 
     pub page_author_url: String,
     pub page_author: String,
+    pub page_email: String,
     pub page_copyright_years: String,
 
     pub page_license: Option<String>,
     pub page_license_code: Option<String>,
 
+    pub page_tags: Vec<DocumentContextTerm>,
+    pub page_categories: Vec<DocumentContextTerm>,
+
     pub sitemap: Option<Vec<DocumentContextSitemapItem>>,
     pub local_sitemap: DocumentContextLocalSitemap,
 
     pub revisions: Vec<DocumentContextRevision>,
+
+    /// First page of the post archive of the document's folder.
+    pub archive: Option<ArchiveContextArchive>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentContextSearch {
+    pub index_url: String,
+    pub script_url: String,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -62,6 +88,13 @@ struct DocumentContextRevision {
     pub content: String,
 }
 
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentContextTerm {
+    pub name: String,
+    pub url: Option<String>,
+}
+
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DocumentContextSiteLink {
@@ -82,6 +115,8 @@ struct DocumentContextSitemapItem {
 struct DocumentContextLocalSitemap {
     pub breadcrumb: Option<Vec<DocumentContextBreadcrumbItem>>,
     pub children: Option<Vec<DocumentContextBreadcrumbItem>>,
+    pub prev: Option<DocumentContextBreadcrumbItem>,
+    pub next: Option<DocumentContextBreadcrumbItem>,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -92,6 +127,20 @@ struct DocumentContextBreadcrumbItem {
     pub description: String,
 }
 
+impl DocumentContextBreadcrumbItem {
+    pub fn from_breadcrumb_item(item: &BreadcrumbItem, site_base_url: &str) -> Self {
+        Self {
+            title: item.title.clone(),
+            url: format!(
+                "{}{}/",
+                site_base_url,
+                item.document_name.folder_path().display()
+            ),
+            description: item.description.clone(),
+        }
+    }
+}
+
 impl DocumentContextSitemapItem {
     pub fn from_sitemap_item(
         item: SitemapItem,
@@ -125,18 +174,142 @@ impl DocumentContextSitemapItem {
     }
 }
 
+/// Copyright years of a document, spanning its creation date, publication
+/// date and revision history, falling back to the site's copyright year.
+/// Empty if there is none.
+fn copyright_years(rendered: &RenderedDocument) -> String {
+    let years = rendered
+        .data
+        .created
+        .iter()
+        .copied()
+        .chain(rendered.data.publish_date())
+        .chain(rendered.data.revisions.keys().copied())
+        .map(|date| date.year())
+        .chain(rendered.site_metadata.config.copyright_year)
+        .collect::<Vec<_>>();
+
+    let (start, end) = match (years.iter().min(), years.iter().max()) {
+        (Some(start), Some(end)) => (*start, *end),
+        _ => return String::new(),
+    };
+
+    if start == end {
+        format!("{}", start)
+    } else {
+        format!("{}-{}", start, end)
+    }
+}
+
+/// Terms of a document, with the URL of their listing page if the site
+/// generates one.
+fn terms(
+    rendered: &RenderedDocument,
+    handlebars: &Handlebars,
+    taxonomy: Taxonomy,
+) -> Vec<DocumentContextTerm> {
+    let site_base_url = &rendered.site_metadata.config.base_url;
+    let has_term_pages = handlebars.has_template(TAXONOMY_TERM_TEMPLATE);
+
+    taxonomy
+        .terms(&rendered.data)
+        .iter()
+        .map(|term| {
+            let slug = utils::slugify(term);
+            DocumentContextTerm {
+                name: term.clone(),
+                url: (has_term_pages && !slug.is_empty())
+                    .then(|| taxonomy.term_url(site_base_url, &slug)),
+            }
+        })
+        .collect()
+}
+
+/// Template of a document: `document/<layout>`, where the layout is the
+/// document's `layout` attribute, or else the layout configured for its
+/// folder or type, falling back to `document/main`.
+fn template(rendered: &RenderedDocument, handlebars: &Handlebars) -> String {
+    let layouts = &rendered.site_metadata.config.layouts;
+    let rel_source_path = &rendered.metadata.rel_source_path;
+
+    let folder_layout = layouts
+        .folders
+        .iter()
+        .filter(|(folder, _)| rel_source_path.starts_with(folder))
+        .max_by_key(|(folder, _)| Path::new(folder).components().count())
+        .map(|(_, layout)| layout);
+
+    let mut types = Vec::new();
+    if rendered.data.name.post.is_some() {
+        types.push("post");
+    }
+    if !rendered.data.specs.is_empty() {
+        types.push("spec");
+    }
+    if let Some(extension) = rel_source_path.extension().and_then(|v| v.to_str()) {
+        types.push(extension);
+    }
+    let type_layout = types.iter().find_map(|typ| layouts.types.get(*typ));
+
+    let Some(layout) = rendered
+        .data
+        .layout
+        .as_ref()
+        .or(folder_layout)
+        .or(type_layout)
+    else {
+        return DEFAULT_TEMPLATE.to_string();
+    };
+
+    let template = format!("document/{}", layout);
+    if handlebars.has_template(&template) {
+        template
+    } else {
+        warn!(
+            "[{}] {}: layout {:?} not found, using {}",
+            rendered.site_metadata.name,
+            rel_source_path.display(),
+            layout,
+            DEFAULT_TEMPLATE
+        );
+        DEFAULT_TEMPLATE.to_string()
+    }
+}
+
 pub fn layout(
     rendered: &RenderedDocument,
     sitemap: &Sitemap,
     local_sitemap: &LocalSitemap,
+    archive: Option<&ArchivePage>,
     handlebars: &Handlebars,
 ) -> Result<String, Error> {
     let site_config = &rendered.site_metadata.config;
 
+    // The site email is only used for documents by the site author, so
+    // that it never ends up on someone else's byline.
+    let page_email = match (&rendered.data.author, &rendered.data.email) {
+        (_, Some(email)) => email.clone(),
+        (None, None) => site_config.email.clone(),
+        (Some(_), None) => String::new(),
+    };
+
     let context = DocumentContext {
         site_title: site_config.title.clone(),
         site_title_only: site_config.title == rendered.data.title,
         site_base_url: site_config.base_url.clone(),
+        site_feed_url: if site_config.feed.enable {
+            Some(format!("{}{}", site_config.base_url, feed::ATOM_FILE_NAME))
+        } else {
+            None
+        },
+        site_search: if site_config.search.enable {
+            Some(DocumentContextSearch {
+                index_url: format!("{}{}", site_config.base_url, search::SEARCH_INDEX_FILE_NAME),
+                script_url: format!("{}{}", site_config.base_url, search::SEARCH_SCRIPT_PATH),
+            })
+        } else {
+            None
+        },
 
         page_title: rendered.data.title.clone(),
         page_description: Some(rendered.data.description.clone()),
@@ -160,13 +333,25 @@ pub fn layout(
         toc: rendered.data.toc.clone(),
         page_content: rendered.data.content.clone(),
 
-        page_author_url: "https://social.pacna.org/@wei".to_string(),
-        page_author: "Wei Tang".to_string(),
-        page_copyright_years: "2019-2026".to_string(),
+        page_author_url: match (&rendered.data.author, &site_config.author_url) {
+            (None, Some(author_url)) => author_url.clone(),
+            _ if page_email.is_empty() => String::new(),
+            _ => format!("mailto:{}", page_email),
+        },
+        page_author: rendered
+            .data
+            .author
+            .clone()
+            .unwrap_or(site_config.author.clone()),
+        page_email,
+        page_copyright_years: copyright_years(rendered),
 
         page_license: rendered.data.license.clone(),
         page_license_code: rendered.data.license_code.clone(),
 
+        page_tags: terms(rendered, handlebars, Taxonomy::Tags),
+        page_categories: terms(rendered, handlebars, Taxonomy::Categories),
+
         sitemap: if site_config.sitemap.enable {
             Some(
                 sitemap
@@ -191,44 +376,44 @@ pub fn layout(
             None
         },
         local_sitemap: DocumentContextLocalSitemap {
-            breadcrumb: if local_sitemap.breadcrumb.len() > 0 {
+            breadcrumb: if !local_sitemap.breadcrumb.is_empty() {
                 Some(
                     local_sitemap
                         .breadcrumb
                         .iter()
-                        .map(|item| DocumentContextBreadcrumbItem {
-                            title: item.title.clone(),
-                            url: format!(
-                                "{}{}/",
-                                site_config.base_url,
-                                item.document_name.folder_path().display()
-                            ),
-                            description: item.description.clone(),
+                        .map(|item| {
+                            DocumentContextBreadcrumbItem::from_breadcrumb_item(
+                                item,
+                                &site_config.base_url,
+                            )
                         })
                         .collect(),
                 )
             } else {
                 None
             },
-            children: if local_sitemap.children.len() > 0 {
+            children: if !local_sitemap.children.is_empty() {
                 Some(
                     local_sitemap
                         .children
                         .iter()
-                        .map(|item| DocumentContextBreadcrumbItem {
-                            title: item.title.clone(),
-                            url: format!(
-                                "{}{}/",
-                                site_config.base_url,
-                                item.document_name.folder_path().display()
-                            ),
-                            description: item.description.clone(),
+                        .map(|item| {
+                            DocumentContextBreadcrumbItem::from_breadcrumb_item(
+                                item,
+                                &site_config.base_url,
+                            )
                         })
                         .collect(),
                 )
             } else {
                 None
             },
+            prev: local_sitemap.prev.as_ref().map(|item| {
+                DocumentContextBreadcrumbItem::from_breadcrumb_item(item, &site_config.base_url)
+            }),
+            next: local_sitemap.next.as_ref().map(|item| {
+                DocumentContextBreadcrumbItem::from_breadcrumb_item(item, &site_config.base_url)
+            }),
         },
 
         revisions: {
@@ -248,9 +433,11 @@ pub fn layout(
                 })
                 .collect()
         },
+
+        archive: archive.map(|page| archive_context(site_config, page)),
     };
 
-    let layouted = handlebars.render("document/main", &context)?;
+    let layouted = handlebars.render(&template(rendered, handlebars), &context)?;
 
     Ok(layouted)
 }
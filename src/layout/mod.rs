@@ -16,6 +16,14 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+mod archive;
 mod document;
+mod not_found;
+mod taxonomy;
 
-pub use self::document::layout as document;
+pub use self::{
+    archive::layout as archive,
+    document::layout as document,
+    not_found::layout as not_found,
+    taxonomy::{index as taxonomy_index, term as taxonomy_term},
+};
@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{Error, site::SiteMetadata};
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+
+pub const NOT_FOUND_TEMPLATE: &str = "404";
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NotFoundContext {
+    pub site_title: String,
+    pub site_url: String,
+    pub site_base_url: String,
+    pub site_links: Vec<NotFoundContextSiteLink>,
+    pub page_path: String,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NotFoundContextSiteLink {
+    pub url: String,
+    pub name: String,
+}
+
+/// Render the `404` layout of a site for a missing page, if the site has
+/// one.
+pub fn layout(
+    site: &SiteMetadata,
+    handlebars: &Handlebars,
+    page_path: &str,
+) -> Result<Option<String>, Error> {
+    if !handlebars.has_template(NOT_FOUND_TEMPLATE) {
+        return Ok(None);
+    }
+
+    let context = NotFoundContext {
+        site_title: site.config.title.clone(),
+        site_url: site.config.url.clone(),
+        site_base_url: site.config.base_url.clone(),
+        site_links: site
+            .config
+            .links
+            .iter()
+            .map(|link| NotFoundContextSiteLink {
+                url: link.url.clone(),
+                name: link.name.clone(),
+            })
+            .collect(),
+        page_path: page_path.to_owned(),
+    };
+
+    Ok(Some(handlebars.render(NOT_FOUND_TEMPLATE, &context)?))
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use super::archive::folder_url;
+use crate::{
+    Error,
+    document::RenderedDocument,
+    site::SiteMetadata,
+    taxonomy::{TAXONOMY_INDEX_TEMPLATE, TAXONOMY_TERM_TEMPLATE, Taxonomy, TaxonomyTerm},
+};
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxonomyTermContext {
+    pub site_title: String,
+    pub site_base_url: String,
+    pub site_links: Vec<TaxonomyContextSiteLink>,
+    pub page_url: String,
+    pub taxonomy: String,
+    pub term: String,
+    pub documents: Vec<TaxonomyContextDocument>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxonomyIndexContext {
+    pub site_title: String,
+    pub site_base_url: String,
+    pub site_links: Vec<TaxonomyContextSiteLink>,
+    pub page_url: String,
+    pub taxonomy: String,
+    pub terms: Vec<TaxonomyContextTerm>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxonomyContextSiteLink {
+    pub url: String,
+    pub name: String,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxonomyContextDocument {
+    pub title: String,
+    pub url: String,
+    pub description: String,
+    pub date: Option<String>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxonomyContextTerm {
+    pub name: String,
+    pub url: Option<String>,
+    pub count: usize,
+}
+
+fn site_links(site: &SiteMetadata) -> Vec<TaxonomyContextSiteLink> {
+    site.config
+        .links
+        .iter()
+        .map(|link| TaxonomyContextSiteLink {
+            url: link.url.clone(),
+            name: link.name.clone(),
+        })
+        .collect()
+}
+
+fn date(document: &RenderedDocument) -> Option<NaiveDate> {
+    document.data.publish_date().or(document.data.created)
+}
+
+/// Render the `taxonomy/term` layout, listing the documents of a term with
+/// the newest first.
+pub fn term(
+    site: &SiteMetadata,
+    handlebars: &Handlebars,
+    taxonomy: Taxonomy,
+    term: &TaxonomyTerm,
+) -> Result<String, Error> {
+    let config = &site.config;
+
+    let mut documents = term.documents.clone();
+    documents.sort_by(|a, b| {
+        date(b)
+            .cmp(&date(a))
+            .then_with(|| a.data.title.cmp(&b.data.title))
+    });
+
+    let context = TaxonomyTermContext {
+        site_title: config.title.clone(),
+        site_base_url: config.base_url.clone(),
+        site_links: site_links(site),
+        page_url: format!(
+            "{}{}/",
+            config.url,
+            taxonomy.term_folder_path(&term.slug).display()
+        ),
+        taxonomy: taxonomy.name().to_string(),
+        term: term.name.clone(),
+        documents: documents
+            .iter()
+            .map(|document| TaxonomyContextDocument {
+                title: document.data.title.clone(),
+                url: folder_url(config, &document.data.name.folder_path()),
+                description: document.data.description.clone(),
+                date: date(document).map(|date| date.format("%Y-%m-%d").to_string()),
+            })
+            .collect(),
+    };
+
+    Ok(handlebars.render(TAXONOMY_TERM_TEMPLATE, &context)?)
+}
+
+/// Render the `taxonomy/index` layout, listing every term of a taxonomy.
+pub fn index(
+    site: &SiteMetadata,
+    handlebars: &Handlebars,
+    taxonomy: Taxonomy,
+    terms: &BTreeMap<String, TaxonomyTerm>,
+) -> Result<String, Error> {
+    let config = &site.config;
+    let has_term_pages = handlebars.has_template(TAXONOMY_TERM_TEMPLATE);
+
+    let context = TaxonomyIndexContext {
+        site_title: config.title.clone(),
+        site_base_url: config.base_url.clone(),
+        site_links: site_links(site),
+        page_url: format!("{}{}/", config.url, taxonomy.name()),
+        taxonomy: taxonomy.name().to_string(),
+        terms: terms
+            .values()
+            .map(|term| TaxonomyContextTerm {
+                name: term.name.clone(),
+                url: has_term_pages.then(|| taxonomy.term_url(&config.base_url, &term.slug)),
+                count: term.documents.len(),
+            })
+            .collect(),
+    };
+
+    Ok(handlebars.render(TAXONOMY_INDEX_TEMPLATE, &context)?)
+}
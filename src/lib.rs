@@ -16,13 +16,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+pub mod archive;
 pub mod asset;
+pub mod cache;
 pub mod document;
 pub mod error;
+pub mod feed;
 pub mod file;
 pub mod layout;
+pub mod manifest;
+pub mod search;
 pub mod site;
 pub mod sitemap;
+pub mod sitemap_xml;
+pub mod specs;
+pub mod taxonomy;
 pub mod utils;
 pub mod variable;
 pub mod workspace;
@@ -19,7 +19,8 @@
 mod command;
 
 use clap::{Parser, Subcommand};
-use eighty::Error;
+use command::build::BuildOptions;
+use eighty::{Error, cache::RenderCache, workspace::WorkspacePath};
 use snafu::ErrorCompat;
 use std::path::Path;
 
@@ -39,6 +40,23 @@ enum Command {
         site: String,
         /// Build target.
         target: String,
+        /// Render every document without using the render cache.
+        #[arg(long)]
+        no_cache: bool,
+        /// Print the files that would be added, changed and deleted in the
+        /// target, without writing anything.
+        #[arg(long)]
+        dry_run: bool,
+        /// Write a manifest.json listing every file of each built site.
+        #[arg(long)]
+        manifest: bool,
+        /// Include drafts and documents scheduled for a later date.
+        #[arg(long)]
+        drafts: bool,
+        /// Also delete hidden files, such as .git or .nojekyll, that the
+        /// build does not produce.
+        #[arg(long)]
+        delete_hidden: bool,
     },
     /// Build multiple projects.
     BuildAll {
@@ -46,11 +64,65 @@ enum Command {
         root: String,
         /// Build target.
         target: String,
+        /// Render every document without using the render cache.
+        #[arg(long)]
+        no_cache: bool,
+        /// Print the files that would be added, changed and deleted in the
+        /// target, without writing anything.
+        #[arg(long)]
+        dry_run: bool,
+        /// Write a manifest.json listing every file of each built site.
+        #[arg(long)]
+        manifest: bool,
+        /// Include drafts and documents scheduled for a later date.
+        #[arg(long)]
+        drafts: bool,
+        /// Also delete hidden files, such as .git or .nojekyll, that the
+        /// build does not produce.
+        #[arg(long)]
+        delete_hidden: bool,
+    },
+    /// Manage the render cache.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Check a project for broken links.
+    Check {
+        /// Site root.
+        site: String,
     },
     /// Serve a project in localhost for development.
     Serve {
         /// Site root.
         site: String,
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Port to listen on, or the first free port after it.
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+    },
+    /// Serve multiple projects in localhost for development, each under its
+    /// base URL.
+    ServeAll {
+        /// Project root.
+        root: String,
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Port to listen on, or the first free port after it.
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheCommand {
+    /// Remove the render cache of a project or project root.
+    Clean {
+        /// Site root or project root.
+        path: String,
     },
 }
 
@@ -60,19 +132,63 @@ fn run() -> Result<(), Error> {
     let args = Cli::parse();
 
     match args.command {
-        Command::Build { site, target } => {
+        Command::Build {
+            site,
+            target,
+            no_cache,
+            dry_run,
+            manifest,
+            drafts,
+            delete_hidden,
+        } => {
             let site_path = Path::new(&site);
             let target_path = Path::new(&target);
-            command::build::build(&site_path, &target_path)?;
+            let options = BuildOptions {
+                no_cache,
+                dry_run,
+                manifest,
+                drafts,
+                delete_hidden,
+            };
+            command::build::build(site_path, target_path, options)?;
         }
-        Command::BuildAll { root, target } => {
+        Command::BuildAll {
+            root,
+            target,
+            no_cache,
+            dry_run,
+            manifest,
+            drafts,
+            delete_hidden,
+        } => {
             let root_path = Path::new(&root);
             let target_path = Path::new(&target);
-            command::build::build_all(&root_path, &target_path)?;
+            let options = BuildOptions {
+                no_cache,
+                dry_run,
+                manifest,
+                drafts,
+                delete_hidden,
+            };
+            command::build::build_all(root_path, target_path, options)?;
+        }
+        Command::Cache {
+            command: CacheCommand::Clean { path },
+        } => {
+            let path = Path::new(&path);
+            RenderCache::clean(path)?;
         }
-        Command::Serve { site } => {
+        Command::Check { site } => {
             let site_path = Path::new(&site);
-            command::serve::serve(&site_path)?;
+            command::check::check(site_path)?;
+        }
+        Command::Serve { site, host, port } => {
+            let site_path = Path::new(&site);
+            command::serve::serve(WorkspacePath::Site(site_path.to_owned()), &host, port)?;
+        }
+        Command::ServeAll { root, host, port } => {
+            let root_path = Path::new(&root);
+            command::serve::serve(WorkspacePath::Root(root_path.to_owned()), &host, port)?;
         }
     }
 
@@ -85,5 +201,6 @@ fn main() {
         if let Some(bt) = ErrorCompat::backtrace(&e) {
             eprintln!("{}", bt);
         }
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    Error,
+    workspace::{SimplePostSite, SimplePostSourceKind},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: usize,
+    pub source: SimplePostSourceKind,
+    pub source_path: Option<PathBuf>,
+}
+
+impl Manifest {
+    /// Manifest of every file of a site, ordered by path.
+    pub fn new(site: &SimplePostSite) -> Result<Self, Error> {
+        let mut files = site
+            .files
+            .iter()
+            .map(|(path, content)| {
+                let source = site.sources.get(path).ok_or(Error::DocumentNotFound)?;
+
+                Ok(ManifestFile {
+                    path: path.clone(),
+                    sha256: format!("{:x}", Sha256::digest(content)),
+                    size: content.len(),
+                    source: source.kind,
+                    source_path: source.path.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Self { files })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut content = serde_json::to_vec_pretty(self)?;
+        content.push(b'\n');
+        Ok(content)
+    }
+}
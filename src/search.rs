@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    Error, utils,
+    workspace::{FullDocument, FullSite},
+};
+use lol_html::{RewriteStrSettings, doc_text, element, html_content::TextType, rewrite_str, text};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+pub const SEARCH_INDEX_FILE_NAME: &str = "search-index.json";
+pub const SEARCH_SCRIPT_PATH: &str = "js/eighty-search.js";
+pub const SEARCH_SCRIPT: &str = include_str!("../assets/js/eighty-search.js");
+
+/// Version of the search index format. Bumped on incompatible changes.
+pub const SEARCH_INDEX_VERSION: usize = 1;
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchIndex {
+    pub version: usize,
+    pub documents: Vec<SearchDocument>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchDocument {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    pub headings: Vec<SearchHeading>,
+    pub tokens: Vec<String>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHeading {
+    pub id: String,
+    pub title: String,
+}
+
+/// Generate the search index of a site, if enabled in its config.
+pub fn files(site: &FullSite) -> Result<HashMap<PathBuf, Vec<u8>>, Error> {
+    let config = &site.site.config;
+    let mut files = HashMap::new();
+
+    if !config.search.enable {
+        return Ok(files);
+    }
+
+    let mut documents = site
+        .documents
+        .iter()
+        .filter(|document| !document.rendered.name.raw)
+        .map(|document| search_document(document, &config.base_url))
+        .collect::<Result<Vec<_>, Error>>()?;
+    documents.sort_by(|a, b| a.url.cmp(&b.url));
+
+    let index = SearchIndex {
+        version: SEARCH_INDEX_VERSION,
+        documents,
+    };
+    files.insert(
+        PathBuf::from(SEARCH_INDEX_FILE_NAME),
+        serde_json::to_vec(&index)?,
+    );
+
+    Ok(files)
+}
+
+fn search_document(document: &FullDocument, base_url: &str) -> Result<SearchDocument, Error> {
+    let text = RefCell::new(String::new());
+    let headings = RefCell::new(Vec::<SearchHeading>::new());
+
+    rewrite_str(
+        &document.body,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!("h1[id], h2[id], h3[id], h4[id], h5[id], h6[id]", |el| {
+                    if let Some(id) = el.get_attribute("id") {
+                        headings.borrow_mut().push(SearchHeading {
+                            id,
+                            title: String::new(),
+                        });
+                    }
+                    Ok(())
+                }),
+                text!("h1[id], h2[id], h3[id], h4[id], h5[id], h6[id]", |t| {
+                    if let Some(heading) = headings.borrow_mut().last_mut() {
+                        heading.title.push_str(&decode_entities(t.as_str()));
+                    }
+                    Ok(())
+                }),
+            ],
+            document_content_handlers: vec![doc_text!(|t| {
+                if t.text_type() == TextType::Data {
+                    let mut text = text.borrow_mut();
+                    text.push_str(&decode_entities(t.as_str()));
+                    if t.last_in_text_node() {
+                        text.push(' ');
+                    }
+                }
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+
+    let mut headings = headings.into_inner();
+    for heading in &mut headings {
+        heading.title = heading
+            .title
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    Ok(SearchDocument {
+        title: document.rendered.title.clone(),
+        description: document.rendered.description.clone(),
+        url: utils::folder_url(base_url, &document.rendered.name.folder_path()),
+        headings,
+        tokens: tokenize(&text.into_inner()),
+    })
+}
+
+/// Split text into unique lowercased alphanumeric tokens, in order of first
+/// appearance. The bundled search script tokenizes queries the same way.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .filter(|token| seen.insert(token.clone()))
+        .collect()
+}
+
+fn decode_entities(s: &str) -> String {
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let c = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                } else if let Some(dec) = name.strip_prefix('#') {
+                    dec.parse().ok().and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+        });
+
+        match (c, entity) {
+            (Some(c), Some((_, end))) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
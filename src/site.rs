@@ -16,9 +16,10 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::Error;
+use crate::{Error, specs::SPECS_SITE_NAME};
 use serde::{Deserialize, Serialize, de::Deserializer};
 use std::{
+    collections::BTreeMap,
     fmt,
     fs::File,
     io::BufReader,
@@ -45,9 +46,32 @@ pub struct SiteConfig {
     pub base_url: String,
     pub author: String,
     pub email: String,
+    pub author_url: Option<String>,
+    /// Copyright year of documents without any date.
+    pub copyright_year: Option<i32>,
     pub sitemap: SiteConfigSitemap,
     #[serde(default)]
     pub links: Vec<SiteConfigLink>,
+    #[serde(default)]
+    pub feed: SiteConfigFeed,
+    #[serde(default)]
+    pub search: SiteConfigSearch,
+    #[serde(default)]
+    pub archive: SiteConfigArchive,
+    #[serde(default)]
+    pub layouts: SiteConfigLayouts,
+    #[serde(default)]
+    pub markdown: SiteConfigMarkdown,
+    /// Processors for additional (or overridden) document file extensions.
+    #[serde(default)]
+    pub processors: BTreeMap<String, SiteConfigProcessor>,
+    /// Seconds after which an external processor is killed.
+    #[serde(default = "default_processor_timeout")]
+    pub processor_timeout: u64,
+}
+
+fn default_processor_timeout() -> u64 {
+    300
 }
 
 fn deserialize_site_url<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -79,6 +103,10 @@ pub struct SiteConfigSitemap {
     pub depth: Option<usize>,
     #[serde(default)]
     pub extra_links: Vec<SiteConfigSitemapExtraLink>,
+    #[serde(default)]
+    pub xml: bool,
+    #[serde(default)]
+    pub robots: bool,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -88,6 +116,76 @@ pub struct SiteConfigSitemapExtraLink {
     pub url: String,
 }
 
+#[derive(Eq, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigFeed {
+    pub enable: bool,
+    #[serde(default)]
+    pub rss: bool,
+    pub limit: Option<usize>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigSearch {
+    pub enable: bool,
+}
+
+/// Default layouts of documents without a `layout` attribute.
+#[derive(Eq, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigLayouts {
+    /// Layouts of the documents in a folder, keyed by folder path relative
+    /// to the site. The longest matching folder wins.
+    #[serde(default)]
+    pub folders: BTreeMap<String, String>,
+    /// Layouts of documents of a type: `post`, `spec` (documents defining
+    /// specifications), or a source file extension.
+    #[serde(default)]
+    pub types: BTreeMap<String, String>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigArchive {
+    /// Number of posts on each page of a post archive.
+    #[serde(default = "default_archive_page_size")]
+    pub page_size: usize,
+}
+
+impl Default for SiteConfigArchive {
+    fn default() -> Self {
+        Self {
+            page_size: default_archive_page_size(),
+        }
+    }
+}
+
+fn default_archive_page_size() -> usize {
+    10
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteConfigProcessor {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Overrides `processorTimeout` for this processor.
+    pub timeout: Option<u64>,
+}
+
+/// Backend used to render Markdown documents.
+#[derive(Eq, Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SiteConfigMarkdown {
+    /// The external `eighty-pandoc` processor.
+    #[default]
+    Pandoc,
+    /// The in-process renderer.
+    Native,
+}
+
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct SiteMetadata {
     pub name: SiteName,
@@ -102,7 +200,7 @@ impl SiteMetadata {
             serde_json::from_reader(BufReader::new(File::open(site_config_path)?))?;
 
         let name = SiteName(site_config.name.clone());
-        if name.0 == "specs" {
+        if name.0 == SPECS_SITE_NAME {
             return Err(Error::ReservedSiteName);
         }
         if let Some(expected_sitename) = expected_sitename
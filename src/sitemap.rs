@@ -16,13 +16,16 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::document::DocumentName;
+use crate::document::{DocumentName, DocumentPostLabel};
 use std::{fmt, ops::Deref};
 
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct LocalSitemap {
     pub breadcrumb: Breadcrumb,
     pub children: Vec<BreadcrumbItem>,
+    /// Previous and next siblings in sitemap order.
+    pub prev: Option<BreadcrumbItem>,
+    pub next: Option<BreadcrumbItem>,
 }
 
 #[derive(Eq, Clone, PartialEq, Debug)]
@@ -103,6 +106,8 @@ impl Sitemap {
             return Some(LocalSitemap {
                 breadcrumb: Breadcrumb(Vec::new()),
                 children: self.0.iter().map(|item| item.item.clone()).collect(),
+                prev: None,
+                next: None,
             });
         }
 
@@ -111,12 +116,14 @@ impl Sitemap {
 
         loop {
             let target = current.iter().find(|item| {
-                item.item.document_name.is_ancestor_of(&document_name)
+                item.item.document_name.is_ancestor_of(document_name)
                     || item.item.document_name == *document_name
             });
 
             if let Some(target) = target {
                 if target.item.document_name == *document_name {
+                    let (prev, next) = siblings(current, document_name);
+
                     return Some(LocalSitemap {
                         breadcrumb: Breadcrumb(breadcrumb),
                         children: target
@@ -124,6 +131,8 @@ impl Sitemap {
                             .iter()
                             .map(|item| item.item.clone())
                             .collect(),
+                        prev,
+                        next,
                     });
                 } else {
                     breadcrumb.push(target.item.clone());
@@ -176,9 +185,9 @@ impl fmt::Display for Sitemap {
 }
 
 fn fmt_sitemap_item(f: &mut fmt::Formatter<'_>, item: &SitemapItem, prefix: &str) -> fmt::Result {
-    write!(
+    writeln!(
         f,
-        "{}- {}: {}\n",
+        "{}- {}: {}",
         prefix, item.item.document_name, item.item.title
     )?;
     for child in &item.children {
@@ -188,14 +197,40 @@ fn fmt_sitemap_item(f: &mut fmt::Formatter<'_>, item: &SitemapItem, prefix: &str
     Ok(())
 }
 
+/// Sitemap order of items: by `order`, then by path, which orders posts by
+/// date.
+fn sort_key(item: &BreadcrumbItem) -> (usize, &Vec<String>, &Option<DocumentPostLabel>) {
+    (
+        item.order.unwrap_or(usize::MAX),
+        &item.document_name.labels,
+        &item.document_name.post,
+    )
+}
+
+/// Previous and next siblings of a document among `items`, in sitemap
+/// order.
+fn siblings(
+    items: &[SitemapItem],
+    document_name: &DocumentName,
+) -> (Option<BreadcrumbItem>, Option<BreadcrumbItem>) {
+    let mut ordered = items.iter().map(|item| &item.item).collect::<Vec<_>>();
+    ordered.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+
+    let Some(index) = ordered
+        .iter()
+        .position(|item| item.document_name == *document_name)
+    else {
+        return (None, None);
+    };
+
+    (
+        index.checked_sub(1).map(|index| ordered[index].clone()),
+        ordered.get(index + 1).map(|item| (*item).clone()),
+    )
+}
+
 fn sort_sitemap_item_vec(items: &mut Vec<SitemapItem>) {
-    items.sort_by_key(|k| {
-        (
-            k.item.order.unwrap_or(usize::MAX),
-            k.item.document_name.labels.clone(),
-            k.item.document_name.post.clone(),
-        )
-    });
+    items.sort_by(|a, b| sort_key(&a.item).cmp(&sort_key(&b.item)));
     for item in items {
         sort_sitemap_item_vec(&mut item.children);
     }
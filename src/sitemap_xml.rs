@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    utils::{self, xml_escape},
+    workspace::{FullDocument, FullSite, FullWorkspace},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::{collections::HashMap, path::PathBuf};
+use tracing::warn;
+
+pub const SITEMAP_FILE_NAME: &str = "sitemap.xml";
+pub const SITEMAP_INDEX_FILE_NAME: &str = "sitemap_index.xml";
+pub const ROBOTS_FILE_NAME: &str = "robots.txt";
+
+/// Generate `sitemap.xml` and `robots.txt` of a site, as configured in
+/// its sitemap config.
+pub fn files(site: &FullSite) -> HashMap<PathBuf, Vec<u8>> {
+    let config = &site.site.config;
+    let mut files = HashMap::new();
+
+    if !config.sitemap.xml {
+        return files;
+    }
+
+    let mut urls = site
+        .documents
+        .iter()
+        .filter(|document| !document.rendered.name.raw)
+        .map(|document| {
+            (
+                utils::folder_url(&config.url, &document.rendered.name.folder_path()),
+                last_modified(document),
+            )
+        })
+        .collect::<Vec<_>>();
+    urls.sort();
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for (url, lastmod) in urls {
+        out.push_str(&format!(
+            "  <url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+            xml_escape(&url),
+            lastmod.format("%Y-%m-%d")
+        ));
+    }
+    out.push_str("</urlset>\n");
+    files.insert(PathBuf::from(SITEMAP_FILE_NAME), out.into_bytes());
+
+    // Crawlers only read `robots.txt` at the root of a host, so it is
+    // pointless for a site under a base path.
+    let robots_path = PathBuf::from(ROBOTS_FILE_NAME);
+    if config.sitemap.robots && config.base_url != "/" {
+        warn!(
+            "[{}] not generating {} for a site with base URL {}",
+            config.name, ROBOTS_FILE_NAME, config.base_url
+        );
+    } else if config.sitemap.robots && !site.files.contains_key(&robots_path) {
+        let robots = format!(
+            "User-agent: *\nAllow: /\n\nSitemap: {}{}\n",
+            config.url, SITEMAP_FILE_NAME
+        );
+        files.insert(robots_path, robots.into_bytes());
+    }
+
+    files
+}
+
+/// Generate a sitemap index referencing the sitemaps of all sites, or
+/// `None` if no site generates one.
+pub fn index(full: &FullWorkspace) -> Option<String> {
+    let mut urls = full
+        .sites
+        .values()
+        .filter(|site| site.site.config.sitemap.xml)
+        .map(|site| format!("{}{}", site.site.config.url, SITEMAP_FILE_NAME))
+        .collect::<Vec<_>>();
+    urls.sort();
+
+    if urls.is_empty() {
+        return None;
+    }
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for url in urls {
+        out.push_str(&format!(
+            "  <sitemap><loc>{}</loc></sitemap>\n",
+            xml_escape(&url)
+        ));
+    }
+    out.push_str("</sitemapindex>\n");
+
+    Some(out)
+}
+
+/// Last modification date of a document, from its latest revision, or the
+/// source file modification time if it has none.
+fn last_modified(document: &FullDocument) -> NaiveDate {
+    match document.rendered.revisions.keys().next_back() {
+        Some(date) => *date,
+        None => DateTime::<Utc>::from(document.metadata.modified).date_naive(),
+    }
+}
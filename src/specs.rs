@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{Error, site::SiteName, utils, workspace::FullWorkspace};
+use handlebars::html_escape;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+use tracing::error;
+
+/// Name of the generated site holding the specification registry.
+pub const SPECS_SITE_NAME: &str = "specs";
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecEntry {
+    pub id: String,
+    pub description: String,
+    pub discuss: String,
+    pub site: String,
+    pub source_path: PathBuf,
+    pub url: String,
+}
+
+/// All specifications across all sites, keyed by their lowercased id.
+#[derive(Eq, Clone, PartialEq, Debug)]
+pub struct SpecRegistry(pub BTreeMap<String, SpecEntry>);
+
+impl SpecRegistry {
+    pub fn new(full: &FullWorkspace) -> Result<Self, Error> {
+        let mut specs = BTreeMap::<String, SpecEntry>::new();
+
+        let mut site_names = full.sites.keys().collect::<Vec<_>>();
+        site_names.sort();
+
+        for site_name in site_names {
+            let full_site = &full.sites[site_name];
+
+            let mut documents = full_site.documents.iter().collect::<Vec<_>>();
+            documents.sort_by_key(|document| &document.metadata.rel_source_path);
+
+            for document in documents {
+                for spec in &document.rendered.specs {
+                    if !is_valid_spec_id(&spec.id) {
+                        error!(
+                            "Invalid spec id {:?} in [{}] {:?}",
+                            spec.id, site_name.0, document.metadata.rel_source_path
+                        );
+                        return Err(Error::InvalidSpecId);
+                    }
+
+                    let entry = SpecEntry {
+                        id: spec.id.clone(),
+                        description: spec.description.clone(),
+                        discuss: spec.discuss.clone(),
+                        site: site_name.0.clone(),
+                        source_path: document.metadata.rel_source_path.clone(),
+                        url: format!(
+                            "{}#{}",
+                            utils::folder_url(
+                                &full_site.site.config.url,
+                                &document.rendered.name.folder_path()
+                            ),
+                            spec.anchor
+                        ),
+                    };
+
+                    if let Some(existing) = specs.get(&spec.id.to_lowercase()) {
+                        error!(
+                            "Duplicate spec id {} in [{}] {:?} and [{}] {:?}",
+                            spec.id,
+                            existing.site,
+                            existing.source_path,
+                            entry.site,
+                            entry.source_path
+                        );
+                        return Err(Error::DuplicateSpecId);
+                    }
+
+                    specs.insert(spec.id.to_lowercase(), entry);
+                }
+            }
+        }
+
+        Ok(Self(specs))
+    }
+
+    pub fn site_name(&self) -> SiteName {
+        SiteName(SPECS_SITE_NAME.to_string())
+    }
+
+    /// Generate files of the specs site, with one landing page per spec, an
+    /// index page, and a JSON listing at `specs.json`.
+    pub fn files(&self) -> Result<HashMap<PathBuf, Vec<u8>>, Error> {
+        let mut files = HashMap::new();
+
+        for (key, spec) in &self.0 {
+            let content = format!(
+                concat!(
+                    "<!DOCTYPE html>\n",
+                    "<html>\n",
+                    "<head>\n",
+                    "<meta charset=\"utf-8\">\n",
+                    "<title>{id}</title>\n",
+                    "<link rel=\"canonical\" href=\"{url}\">\n",
+                    "<meta http-equiv=\"refresh\" content=\"0; url={url}\">\n",
+                    "</head>\n",
+                    "<body>\n",
+                    "<h1>{id}</h1>\n",
+                    "<p>{description}</p>\n",
+                    "<p><a href=\"{url}\">Specification</a> (<a href=\"{discuss}\">Discuss</a>)</p>\n",
+                    "</body>\n",
+                    "</html>\n"
+                ),
+                id = html_escape(&spec.id),
+                url = html_escape(&spec.url),
+                description = html_escape(&spec.description),
+                discuss = html_escape(&spec.discuss),
+            );
+
+            files.insert(PathBuf::from(key).join("index.html"), content.into_bytes());
+        }
+
+        let items = self
+            .0
+            .iter()
+            .map(|(key, spec)| {
+                format!(
+                    "<li><a href=\"{}/\">{}</a>: {}</li>\n",
+                    html_escape(key),
+                    html_escape(&spec.id),
+                    html_escape(&spec.description)
+                )
+            })
+            .collect::<String>();
+        let index = format!(
+            concat!(
+                "<!DOCTYPE html>\n",
+                "<html>\n",
+                "<head>\n",
+                "<meta charset=\"utf-8\">\n",
+                "<title>Specifications</title>\n",
+                "</head>\n",
+                "<body>\n",
+                "<h1>Specifications</h1>\n",
+                "<ul>\n",
+                "{}",
+                "</ul>\n",
+                "</body>\n",
+                "</html>\n"
+            ),
+            items
+        );
+        files.insert(PathBuf::from("index.html"), index.into_bytes());
+
+        let listing = serde_json::to_vec_pretty(&self.0.values().collect::<Vec<_>>())?;
+        files.insert(PathBuf::from("specs.json"), listing);
+
+        Ok(files)
+    }
+}
+
+/// Whether a spec id can be used as the folder of its landing page: ASCII
+/// letters, digits, `-`, `_` and `.`, not starting with a dot, and not
+/// naming one of the other generated files.
+fn is_valid_spec_id(id: &str) -> bool {
+    let key = id.to_lowercase();
+
+    !key.is_empty()
+        && !key.starts_with('.')
+        && !key.contains("..")
+        && key != "index.html"
+        && key != "specs.json"
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_ids() {
+        for id in ["CP-1", "core_paper.2", "a"] {
+            assert!(is_valid_spec_id(id), "{}", id);
+        }
+        for id in [
+            "",
+            ".",
+            "..",
+            "a..b",
+            ".hidden",
+            "a/b",
+            "../x",
+            "a b",
+            "ü",
+            "index.html",
+            "Specs.json",
+        ] {
+            assert!(!is_valid_spec_id(id), "{}", id);
+        }
+    }
+}
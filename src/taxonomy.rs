@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    Error,
+    document::{RenderedData, RenderedDocument},
+    layout,
+    site::SiteMetadata,
+    utils,
+};
+use handlebars::Handlebars;
+use std::{collections::BTreeMap, path::PathBuf};
+
+pub const TAXONOMY_TERM_TEMPLATE: &str = "taxonomy/term";
+pub const TAXONOMY_INDEX_TEMPLATE: &str = "taxonomy/index";
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug)]
+pub enum Taxonomy {
+    Tags,
+    Categories,
+}
+
+impl Taxonomy {
+    pub const ALL: [Taxonomy; 2] = [Self::Tags, Self::Categories];
+
+    /// Name of the taxonomy, which is also the folder of its pages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tags => "tags",
+            Self::Categories => "categories",
+        }
+    }
+
+    pub fn terms(self, data: &RenderedData) -> &[String] {
+        match self {
+            Self::Tags => &data.tags,
+            Self::Categories => &data.categories,
+        }
+    }
+
+    /// Folder path of the listing page of a term, given its slug.
+    pub fn term_folder_path(self, slug: &str) -> PathBuf {
+        PathBuf::from(self.name()).join(slug)
+    }
+
+    pub fn term_url(self, site_base_url: &str, slug: &str) -> String {
+        format!(
+            "{}{}/",
+            site_base_url,
+            self.term_folder_path(slug).display()
+        )
+    }
+}
+
+/// Documents of one term of a taxonomy.
+pub struct TaxonomyTerm<'a> {
+    pub name: String,
+    pub slug: String,
+    pub documents: Vec<&'a RenderedDocument>,
+}
+
+/// Terms of a taxonomy used by any of `documents`, keyed by slug. Terms
+/// with the same slug are merged, named after their first use, and terms
+/// without any letter or digit, whose slug is empty, are ignored.
+pub fn terms<'a>(
+    taxonomy: Taxonomy,
+    documents: &[&'a RenderedDocument],
+) -> BTreeMap<String, TaxonomyTerm<'a>> {
+    let mut terms = BTreeMap::<String, TaxonomyTerm>::new();
+
+    for document in documents.iter().filter(|v| !v.data.name.raw) {
+        for term in taxonomy.terms(&document.data) {
+            let slug = utils::slugify(term);
+            if slug.is_empty() {
+                continue;
+            }
+
+            let documents = &mut terms
+                .entry(slug.clone())
+                .or_insert_with(|| TaxonomyTerm {
+                    name: term.clone(),
+                    slug,
+                    documents: Vec::new(),
+                })
+                .documents;
+            // Documents are visited one at a time, so a document listing
+            // several terms with the same slug is always the last one.
+            if !documents
+                .last()
+                .is_some_and(|last| std::ptr::eq(*last, *document))
+            {
+                documents.push(document);
+            }
+        }
+    }
+
+    terms
+}
+
+/// Listing pages of every taxonomy term, and an index page of each
+/// taxonomy, keyed by output path. Pages are only generated if the site has
+/// the corresponding `taxonomy/term` and `taxonomy/index` layouts.
+pub fn pages(
+    site: &SiteMetadata,
+    documents: &[&RenderedDocument],
+    handlebars: &Handlebars,
+) -> Result<BTreeMap<PathBuf, String>, Error> {
+    let mut pages = BTreeMap::new();
+
+    for taxonomy in Taxonomy::ALL {
+        let terms = terms(taxonomy, documents);
+        if terms.is_empty() {
+            continue;
+        }
+
+        if handlebars.has_template(TAXONOMY_TERM_TEMPLATE) {
+            for term in terms.values() {
+                pages.insert(
+                    taxonomy.term_folder_path(&term.slug).join("index.html"),
+                    layout::taxonomy_term(site, handlebars, taxonomy, term)?,
+                );
+            }
+        }
+
+        if handlebars.has_template(TAXONOMY_INDEX_TEMPLATE) {
+            pages.insert(
+                PathBuf::from(taxonomy.name()).join("index.html"),
+                layout::taxonomy_index(site, handlebars, taxonomy, &terms)?,
+            );
+        }
+    }
+
+    Ok(pages)
+}
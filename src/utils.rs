@@ -1,3 +1,4 @@
+use crate::Error;
 use std::path::{Component, Path, PathBuf};
 
 /// Normalize a path, removing things like `.` and `..`.
@@ -41,3 +42,94 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     }
     ret
 }
+
+/// Compute a relative link from folder `base` to `target`, as used in
+/// rendered HTML.
+pub fn relative_link(target: &Path, base: &Path) -> Result<String, Error> {
+    let rel = pathdiff::diff_paths(target, base).ok_or(Error::PathDiffFailed)?;
+
+    Ok(rel.to_string_lossy().into_owned())
+}
+
+/// URL of the page of a document folder under `base`, which ends with `/`,
+/// as is `base` itself for the root folder.
+pub fn folder_url(base: &str, folder_path: &Path) -> String {
+    if folder_path.as_os_str().is_empty() {
+        base.to_owned()
+    } else {
+        format!("{}{}/", base, folder_path.display())
+    }
+}
+
+/// Escape a string for use in XML text and attribute values.
+pub fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Lowercase URL path segment for a term, with runs of other characters
+/// than letters and digits replaced by `-`.
+pub fn slugify(s: &str) -> String {
+    let mut slug = String::with_capacity(s.len());
+
+    for c in s.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_urls() {
+        assert_eq!(
+            folder_url("https://example.org/", Path::new("")),
+            "https://example.org/"
+        );
+        assert_eq!(folder_url("/docs/", Path::new("a/b")), "/docs/a/b/");
+    }
+
+    #[test]
+    fn slugify_terms() {
+        assert_eq!(slugify("Rust Lang"), "rust-lang");
+        assert_eq!(slugify("  C++ / Rust!  "), "c-rust");
+        assert_eq!(slugify("rust-lang"), slugify("Rust Lang"));
+    }
+
+    #[test]
+    fn slugify_empty() {
+        assert_eq!(slugify(""), "");
+        assert_eq!(slugify("++"), "");
+        assert_eq!(slugify(" - "), "");
+    }
+
+    #[test]
+    fn slugify_unicode() {
+        assert_eq!(slugify("Über Straße"), "über-straße");
+        assert_eq!(slugify("ΣΊΣΥΦΟΣ"), "σίσυφοσ");
+        assert_eq!(slugify("日本語 テキスト"), "日本語-テキスト");
+    }
+}
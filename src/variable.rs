@@ -16,8 +16,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::Error;
+use crate::{Error, document::DocumentName, site::SiteConfig, utils};
 use regex::Regex;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tracing::{error, warn};
+
+/// Target of cross references: a document, and whether it is part of the
+/// output. Links to unpublished documents are not emitted.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Xref {
+    pub name: DocumentName,
+    pub published: bool,
+}
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub struct Variable {
@@ -29,7 +42,7 @@ pub struct Variable {
 pub fn search(content: &str) -> Result<Vec<Variable>, Error> {
     let mut matches = Vec::new();
 
-    let re = Regex::new(r"@@(.+)@@")?;
+    let re = Regex::new(r"@@([^@\n]+)@@")?;
 
     for caps in re.captures_iter(content) {
         let full = caps
@@ -57,3 +70,191 @@ pub fn search(content: &str) -> Result<Vec<Variable>, Error> {
 
     Ok(matches)
 }
+
+/// Resolve all cross-reference variables in `content`.
+///
+/// `current` is the document the content belongs to, used to compute
+/// relative locations. Targets are looked up in `xrefs`, which maps
+/// site-relative source paths to documents. References to unpublished
+/// documents are replaced with `#`, with a warning.
+pub fn resolve(
+    content: &str,
+    variables: &[Variable],
+    current: &DocumentName,
+    xrefs: &HashMap<PathBuf, Xref>,
+    config: &SiteConfig,
+) -> Result<String, Error> {
+    let mut resolved = content.to_owned();
+
+    // Raw documents are files rather than folders, so relative locations
+    // are computed from their parent folder.
+    let base = if current.raw {
+        current
+            .folder_path()
+            .parent()
+            .map(|p| p.to_owned())
+            .unwrap_or_default()
+    } else {
+        current.folder_path()
+    };
+
+    for variable in variables {
+        let value = match variable.name.as_str() {
+            "XREFLINK" | "XREFPATH" | "XREFFULLLINK" => {
+                let target = resolve_xref(variable, xrefs)?;
+                if !target.published {
+                    warn!(
+                        "Cross reference {} in {} to an unpublished document",
+                        variable.full, current
+                    );
+                    "#".to_string()
+                } else if variable.name == "XREFLINK" {
+                    utils::relative_link(&target.name.folder_path(), &base)?
+                } else if variable.name == "XREFPATH" {
+                    utils::relative_link(&target.name.path(), &base)?
+                } else {
+                    utils::folder_url(&config.url, &target.name.folder_path())
+                }
+            }
+            _ => {
+                error!("Unsupported variable {} in {}", variable.full, current);
+                return Err(Error::UnsupportedVariable);
+            }
+        };
+
+        resolved = resolved.replace(&variable.full, &value);
+    }
+
+    Ok(resolved)
+}
+
+fn resolve_xref<'a>(
+    variable: &Variable,
+    xrefs: &'a HashMap<PathBuf, Xref>,
+) -> Result<&'a Xref, Error> {
+    let target = variable
+        .arguments
+        .as_ref()
+        .ok_or(Error::UnresolvedXreflink)?;
+
+    xrefs
+        .get(&utils::normalize_path(Path::new(target)))
+        .ok_or_else(|| {
+            error!("Unresolved cross reference {}", variable.full);
+            Error::UnresolvedXreflink
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(labels: &[&str]) -> DocumentName {
+        DocumentName {
+            id: None,
+            labels: labels.iter().map(|label| label.to_string()).collect(),
+            post: None,
+            raw: false,
+        }
+    }
+
+    fn config() -> SiteConfig {
+        serde_json::from_str(
+            r#"{
+                "name": "test",
+                "title": "Test",
+                "url": "https://example.org/",
+                "baseUrl": "/",
+                "author": "A",
+                "email": "a@example.org",
+                "sitemap": {"enable": false, "depth": 1}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn xrefs() -> HashMap<PathBuf, Xref> {
+        HashMap::from([
+            (
+                PathBuf::from("guide/intro.md"),
+                Xref {
+                    name: name(&["guide", "intro"]),
+                    published: true,
+                },
+            ),
+            (
+                PathBuf::from("draft.md"),
+                Xref {
+                    name: name(&["draft"]),
+                    published: false,
+                },
+            ),
+        ])
+    }
+
+    fn resolve_str(content: &str, current: &DocumentName) -> Result<String, Error> {
+        resolve(content, &search(content)?, current, &xrefs(), &config())
+    }
+
+    #[test]
+    fn search_variables() {
+        let variables = search("a @@XREFLINK:b.md@@ c @@OTHER@@").unwrap();
+        assert_eq!(
+            variables,
+            vec![
+                Variable {
+                    full: "@@XREFLINK:b.md@@".to_string(),
+                    name: "XREFLINK".to_string(),
+                    arguments: Some("b.md".to_string()),
+                },
+                Variable {
+                    full: "@@OTHER@@".to_string(),
+                    name: "OTHER".to_string(),
+                    arguments: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_xrefs() {
+        let current = name(&["about"]);
+        assert_eq!(
+            resolve_str("@@XREFLINK:guide/intro.md@@", &current).unwrap(),
+            "../guide/intro"
+        );
+        assert_eq!(
+            resolve_str("@@XREFPATH:./guide/../guide/intro.md@@", &current).unwrap(),
+            "../guide/intro/index.html"
+        );
+        assert_eq!(
+            resolve_str("@@XREFFULLLINK:guide/intro.md@@", &current).unwrap(),
+            "https://example.org/guide/intro/"
+        );
+    }
+
+    #[test]
+    fn resolve_unpublished_xref() {
+        assert_eq!(
+            resolve_str("<a href=\"@@XREFLINK:draft.md@@\">", &name(&[])).unwrap(),
+            "<a href=\"#\">"
+        );
+    }
+
+    #[test]
+    fn resolve_unresolved_xref() {
+        let current = name(&[]);
+        assert!(matches!(
+            resolve_str("@@XREFLINK:missing.md@@", &current),
+            Err(Error::UnresolvedXreflink)
+        ));
+        assert!(matches!(
+            resolve_str("@@XREFLINK@@", &current),
+            Err(Error::UnresolvedXreflink)
+        ));
+        assert!(matches!(
+            resolve_str("@@UNKNOWN:guide/intro.md@@", &current),
+            Err(Error::UnsupportedVariable)
+        ));
+    }
+}
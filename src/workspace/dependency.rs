@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// This file is part of Eighty.
+//
+// Copyright (c) 2021 Wei Tang.
+//
+// Eighty is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Eighty is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Eighty. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{document::RenderedDocument, site::SiteMetadata};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Files read while rendering the documents of a site, and the documents
+/// that depend on them.
+#[derive(Eq, Clone, PartialEq, Debug, Default)]
+pub struct DependencyGraph {
+    /// Modification time of each input when its dependents were rendered,
+    /// `None` if it did not exist.
+    inputs: HashMap<PathBuf, Option<SystemTime>>,
+    /// Source paths of the documents depending on each input.
+    dependents: HashMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl DependencyGraph {
+    pub fn new(site: &SiteMetadata, documents: &HashMap<PathBuf, RenderedDocument>) -> Self {
+        let mut graph = Self::default();
+
+        for (rel_source_path, document) in documents {
+            for dependency in &document.data.dependencies {
+                graph
+                    .inputs
+                    .entry(dependency.clone())
+                    .or_insert_with(|| modified(&site.source_path, dependency));
+                graph
+                    .dependents
+                    .entry(dependency.clone())
+                    .or_default()
+                    .insert(rel_source_path.clone());
+            }
+        }
+
+        graph
+    }
+
+    /// Documents depending on an input.
+    pub fn dependents(&self, input: &Path) -> impl Iterator<Item = &PathBuf> {
+        self.dependents.get(input).into_iter().flatten()
+    }
+
+    /// Documents depending on an input that changed since they were
+    /// rendered.
+    pub fn invalidated(&self, site: &SiteMetadata) -> HashSet<PathBuf> {
+        self.inputs
+            .iter()
+            .filter(|(input, stamp)| modified(&site.source_path, input) != **stamp)
+            .flat_map(|(input, _)| self.dependents(input).cloned())
+            .collect()
+    }
+}
+
+fn modified(site_path: &Path, input: &Path) -> Option<SystemTime> {
+    fs::metadata(site_path.join(input))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
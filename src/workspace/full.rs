@@ -17,19 +17,26 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-    Error,
+    Error, archive,
     asset::AssetStore,
-    document::{DocumentMetadata, DocumentName, RenderedData},
+    document::{DocumentMetadata, RenderedData, RenderedDocument},
     file::FileMetadata,
-    layout,
+    layout, search,
     site::{SiteMetadata, SiteName},
-    sitemap::{BreadcrumbItem, LocalSitemap, Sitemap},
-    utils,
-    variable::{self, Variable},
+    sitemap::{Breadcrumb, BreadcrumbItem, LocalSitemap, Sitemap},
+    taxonomy, utils,
+    variable::{self, Variable, Xref},
     workspace::{RenderedSite, RenderedWorkspace, WorkspacePath},
 };
-use lol_html::{RewriteStrSettings, element, rewrite_str};
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use chrono::{Local, NaiveDate};
+use lol_html::{RewriteStrSettings, element, html_content::ContentType, rewrite_str};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Arc,
+};
+use tracing::{error, warn};
 
 pub struct FullWorkspace {
     pub path: WorkspacePath,
@@ -37,11 +44,20 @@ pub struct FullWorkspace {
 }
 
 impl FullWorkspace {
-    pub fn new(rendered: &RenderedWorkspace) -> Result<Self, Error> {
-        let sites = rendered
-            .sites
-            .iter()
-            .map(|(name, site)| Ok((name.clone(), FullSite::new(&site)?)))
+    /// Lay out every site. Drafts and documents scheduled for a later
+    /// date are left out unless `drafts` is set.
+    pub fn new(rendered: &RenderedWorkspace, drafts: bool) -> Result<Self, Error> {
+        // Sites are processed in name order and the results collected in
+        // that order, so that the first error reported does not depend on
+        // scheduling.
+        let mut site_names = rendered.sites.keys().collect::<Vec<_>>();
+        site_names.sort();
+
+        let sites = site_names
+            .into_par_iter()
+            .map(|name| Ok((name.clone(), FullSite::new(&rendered.sites[name], drafts)?)))
+            .collect::<Vec<Result<_, Error>>>()
+            .into_iter()
             .collect::<Result<HashMap<SiteName, FullSite>, Error>>()?;
 
         Ok(Self {
@@ -55,119 +71,83 @@ pub struct FullSite {
     pub site: Arc<SiteMetadata>,
     pub documents: Vec<FullDocument>,
     pub files: Arc<HashMap<PathBuf, FileMetadata>>,
-    pub xrefs: HashMap<PathBuf, DocumentName>,
+    pub xrefs: HashMap<PathBuf, Xref>,
     pub sitemap: Sitemap,
     pub assets: AssetStore,
+    /// Pages generated from the documents, such as taxonomy listings, keyed
+    /// by output path.
+    pub pages: BTreeMap<PathBuf, String>,
 }
 
 impl FullSite {
-    pub fn new(rendered: &RenderedSite) -> Result<Self, Error> {
-        let assets = AssetStore::new(&rendered.site.source_path)?;
+    pub fn new(rendered: &RenderedSite, drafts: bool) -> Result<Self, Error> {
+        let mut assets = AssetStore::new(&rendered.site.source_path)?;
+        if rendered.site.config.search.enable {
+            assets.insert_bundled(search::SEARCH_SCRIPT_PATH, search::SEARCH_SCRIPT.as_bytes());
+        }
 
-        let name_titles = rendered
+        let today = Local::now().date_naive();
+        let mut documents = rendered
             .documents
+            .values()
+            .filter(|v| drafts || v.data.is_published(today))
+            .collect::<Vec<_>>();
+        documents.sort_by(|a, b| a.metadata.rel_source_path.cmp(&b.metadata.rel_source_path));
+
+        let name_titles = documents
             .iter()
-            .map(|(_, v)| BreadcrumbItem {
+            .filter(|v| !v.data.name.raw)
+            .map(|v| BreadcrumbItem {
                 document_name: v.data.name.clone(),
                 title: v.data.sitemap_title.clone().unwrap_or(v.data.title.clone()),
                 description: v.data.description.clone(),
-                order: v.data.order.clone(),
+                order: v.data.order,
             })
             .collect::<Vec<_>>();
 
         let sitemap = Sitemap::from(name_titles.clone());
 
+        // Unpublished documents are kept so that references to them can be
+        // told apart from broken ones.
         let mut xrefs = HashMap::new();
-        for (_, document) in &rendered.documents {
+        for document in rendered.documents.values() {
             let rel_path = document
                 .metadata
                 .source_path
                 .strip_prefix(&rendered.site.source_path)?;
-            xrefs.insert(rel_path.to_owned(), document.data.name.clone());
+            xrefs.insert(
+                rel_path.to_owned(),
+                Xref {
+                    name: document.data.name.clone(),
+                    published: drafts || document.data.is_published(today),
+                },
+            );
         }
 
-        let full_documents = rendered
-            .documents
-            .iter()
-            .map(|(_, v)| {
-                let local_sitemap = sitemap.local(&v.data.name).ok_or(Error::DocumentNotFound)?;
-
-                let content = layout::document(&v, &sitemap, &local_sitemap, &assets.handlebars)?;
-                let variables = variable::search(&content)?;
-
-                let rewrote_content = rewrite_str(
-                    &content,
-                    RewriteStrSettings {
-                        element_content_handlers: vec![element!("a[href]", |el| {
-                            if let Some(href) = el.get_attribute("href")
-                                && !href.starts_with("/")
-                                && !href.starts_with("http:")
-                                && !href.starts_with("https:")
-                                && !href.starts_with("mailto:")
-                                && !href.starts_with("@@")
-                                && !href.starts_with("#")
-                            {
-                                let (href_target, section_target) = if href.contains("::") {
-                                    let mut s = href.splitn(2, "::");
-                                    let href_target = s.next().ok_or(Error::MalformedLink)?;
-                                    let mut section_target =
-                                        s.next().ok_or(Error::MalformedLink)?;
-                                    while let Some(s) = section_target.strip_prefix("*") {
-                                        section_target = s;
-                                    }
-                                    let section_target =
-                                        section_target.trim().to_lowercase().replace(" ", "-");
-                                    (href_target.to_string(), Some(section_target.to_string()))
-                                } else if href.contains("#") {
-                                    let mut s = href.splitn(2, "::");
-                                    let href_target = s.next().ok_or(Error::MalformedLink)?;
-                                    let section_target = s.next().ok_or(Error::MalformedLink)?;
-                                    (href_target.to_string(), Some(section_target.to_string()))
-                                } else {
-                                    (href, None)
-                                };
-
-                                let source_target = utils::normalize_path(
-                                    &v.metadata.rel_source_path.join("..").join(href_target),
-                                );
-
-                                if let Some(document_name) = xrefs.get(&source_target) {
-                                    let render_target = document_name.folder_path();
-                                    let rel_render_target = pathdiff::diff_paths(
-                                        &render_target,
-                                        &v.data.name.folder_path(),
-                                    )
-                                    .ok_or(Error::PathDiffFailed)?;
-                                    let target_with_section = match section_target {
-                                        Some(section_target) => format!(
-                                            "{}#{}",
-                                            rel_render_target.to_string_lossy(),
-                                            section_target
-                                        ),
-                                        None => format!("{}", rel_render_target.to_string_lossy()),
-                                    };
-
-                                    el.set_attribute("href", &target_with_section)?;
-                                }
-                            }
-
-                            Ok(())
-                        })],
-                        ..RewriteStrSettings::new()
-                    },
-                )?;
-
-                Ok(FullDocument {
-                    site_metadata: v.site_metadata.clone(),
-                    metadata: v.metadata.clone(),
-                    rendered: v.data.clone(),
-                    content: rewrote_content,
-                    variables,
-                    local_sitemap,
-                })
+        let archives = archive::folders(&documents);
+
+        let full_documents = documents
+            .par_iter()
+            .map(|v| {
+                full_document(v, &sitemap, &xrefs, &archives, &assets, today)
+                    .map_err(|err| err.in_document(&v.metadata.rel_source_path))
             })
+            .collect::<Vec<Result<FullDocument, Error>>>()
+            .into_iter()
             .collect::<Result<Vec<FullDocument>, Error>>()?;
 
+        let mut pages = taxonomy::pages(&rendered.site, &documents, &assets.handlebars)?;
+        for (page_path, page) in archive::pages(&rendered.site, &documents, &assets.handlebars)? {
+            if pages.contains_key(&page_path) {
+                error!(
+                    "[{}] Archive page {:?} conflicts with a taxonomy page",
+                    rendered.site.name.0, page_path
+                );
+                return Err(Error::PageConflict);
+            }
+            pages.insert(page_path, page);
+        }
+
         Ok(Self {
             site: rendered.site.clone(),
             documents: full_documents,
@@ -175,16 +155,220 @@ impl FullSite {
             xrefs,
             sitemap,
             assets,
+            pages,
         })
     }
 }
 
+fn full_document(
+    v: &RenderedDocument,
+    sitemap: &Sitemap,
+    xrefs: &HashMap<PathBuf, Xref>,
+    archives: &BTreeMap<Vec<String>, Vec<&RenderedDocument>>,
+    assets: &AssetStore,
+    today: NaiveDate,
+) -> Result<FullDocument, Error> {
+    let body_variables = variable::search(&v.data.content)?;
+    let body = if v.data.name.raw {
+        v.data.content.clone()
+    } else {
+        rewrite_links(&v.data.content, v, xrefs)?
+    };
+    let body = variable::resolve(
+        &body,
+        &body_variables,
+        &v.data.name,
+        xrefs,
+        &v.site_metadata.config,
+    )?;
+
+    if v.data.name.raw {
+        return Ok(FullDocument {
+            site_metadata: v.site_metadata.clone(),
+            metadata: v.metadata.clone(),
+            rendered: v.data.clone(),
+            content: body.clone(),
+            body,
+            variables: body_variables,
+            local_sitemap: LocalSitemap {
+                breadcrumb: Breadcrumb(Vec::new()),
+                children: Vec::new(),
+                prev: None,
+                next: None,
+            },
+        });
+    }
+
+    let local_sitemap = sitemap.local(&v.data.name).ok_or(Error::DocumentNotFound)?;
+
+    let archive = if v.data.name.post.is_none() && v.data.name.id.is_none() {
+        archives.get(&v.data.name.labels).and_then(|posts| {
+            archive::paginate(
+                v.data.name.labels.iter().collect(),
+                None,
+                None,
+                posts,
+                v.site_metadata.config.archive.page_size,
+            )
+            .into_iter()
+            .next()
+        })
+    } else {
+        None
+    };
+
+    let content = layout::document(
+        v,
+        sitemap,
+        &local_sitemap,
+        archive.as_ref(),
+        &assets.handlebars,
+    )?;
+    let variables = variable::search(&content)?;
+
+    let rewrote_content = rewrite_links(&content, v, xrefs)?;
+    let rewrote_content = if v.data.is_published(today) {
+        rewrote_content
+    } else {
+        insert_unpublished_banner(&rewrote_content, &v.data)?
+    };
+
+    let resolved_content = variable::resolve(
+        &rewrote_content,
+        &variables,
+        &v.data.name,
+        xrefs,
+        &v.site_metadata.config,
+    )?;
+
+    Ok(FullDocument {
+        site_metadata: v.site_metadata.clone(),
+        metadata: v.metadata.clone(),
+        rendered: v.data.clone(),
+        content: resolved_content,
+        body,
+        variables,
+        local_sitemap,
+    })
+}
+
+/// Rewrite relative links to source files into links to the rendered
+/// documents. Links to unpublished documents lose their `href`, with a
+/// warning.
+fn rewrite_links(
+    content: &str,
+    document: &RenderedDocument,
+    xrefs: &HashMap<PathBuf, Xref>,
+) -> Result<String, Error> {
+    let rewrote_content = rewrite_str(
+        content,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("a[href]", |el| {
+                if let Some(href) = el.get_attribute("href")
+                    && !href.starts_with("/")
+                    && !href.starts_with("http:")
+                    && !href.starts_with("https:")
+                    && !href.starts_with("mailto:")
+                    && !href.starts_with("@@")
+                    && !href.starts_with("#")
+                {
+                    let (href_target, section_target) = if href.contains("::") {
+                        let mut s = href.splitn(2, "::");
+                        let href_target = s.next().ok_or(Error::MalformedLink)?;
+                        let mut section_target = s.next().ok_or(Error::MalformedLink)?;
+                        while let Some(s) = section_target.strip_prefix("*") {
+                            section_target = s;
+                        }
+                        let section_target = section_target.trim().to_lowercase().replace(" ", "-");
+                        (href_target.to_string(), Some(section_target.to_string()))
+                    } else if href.contains("#") {
+                        let mut s = href.splitn(2, "#");
+                        let href_target = s.next().ok_or(Error::MalformedLink)?;
+                        let section_target = s.next().ok_or(Error::MalformedLink)?;
+                        (href_target.to_string(), Some(section_target.to_string()))
+                    } else {
+                        (href, None)
+                    };
+
+                    let source_target = utils::normalize_path(
+                        &document
+                            .metadata
+                            .rel_source_path
+                            .join("..")
+                            .join(href_target),
+                    );
+
+                    match xrefs.get(&source_target) {
+                        Some(xref) if !xref.published => {
+                            warn!(
+                                "Link in {} to unpublished document {}",
+                                document.data.name,
+                                source_target.display()
+                            );
+                            el.remove_attribute("href");
+                        }
+                        Some(xref) => {
+                            let rel_render_target = utils::relative_link(
+                                &xref.name.folder_path(),
+                                &document.data.name.folder_path(),
+                            )?;
+                            let target_with_section = match section_target {
+                                Some(section_target) => {
+                                    format!("{}#{}", rel_render_target, section_target)
+                                }
+                                None => rel_render_target,
+                            };
+
+                            el.set_attribute("href", &target_with_section)?;
+                        }
+                        None => (),
+                    }
+                }
+
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+
+    Ok(rewrote_content)
+}
+
+/// Insert a banner at the start of the page of a document that is only
+/// shown because drafts were requested.
+fn insert_unpublished_banner(content: &str, data: &RenderedData) -> Result<String, Error> {
+    let message = match data.publish_date() {
+        Some(date) if !data.draft => format!("Scheduled: this page is published on {}.", date),
+        _ => "Draft: this page is not published.".to_string(),
+    };
+    let banner = format!(
+        "<div class=\"eighty-unpublished-banner\" style=\"position:sticky;top:0;\
+         z-index:2147483646;padding:0.5em 1em;background:#ffd54f;color:#000;\
+         font:bold 14px/1.5 sans-serif;text-align:center;\">{}</div>",
+        message
+    );
+
+    Ok(rewrite_str(
+        content,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("body", |el| {
+                el.prepend(&banner, ContentType::Html);
+                Ok(())
+            })],
+            ..RewriteStrSettings::new()
+        },
+    )?)
+}
+
 #[derive(Eq, Clone, PartialEq, Debug)]
 pub struct FullDocument {
     pub site_metadata: Arc<SiteMetadata>,
     pub metadata: Arc<DocumentMetadata>,
     pub rendered: Arc<RenderedData>,
     pub content: String,
+    /// Document content with links rewritten and variables resolved, but
+    /// without layout.
+    pub body: String,
     pub local_sitemap: LocalSitemap,
     pub variables: Vec<Variable>,
 }
@@ -39,8 +39,8 @@ pub enum WorkspacePath {
 impl WorkspacePath {
     pub fn path(&self) -> &Path {
         match self {
-            Self::Root(path) => &path,
-            Self::Site(path) => &path,
+            Self::Root(path) => path,
+            Self::Site(path) => path,
         }
     }
 }
@@ -89,7 +89,7 @@ impl MetadatadWorkspace {
     pub fn new_single(site_path: &Path) -> Result<(Self, SiteName), Error> {
         let mut sites = HashMap::new();
 
-        let site = Arc::new(SiteMetadata::new(None, &site_path)?);
+        let site = Arc::new(SiteMetadata::new(None, site_path)?);
         let site_name = site.name.clone();
         let item = MetadatadSite::new(site.clone())?;
 
@@ -132,7 +132,7 @@ impl MetadatadSite {
                     return true;
                 }
 
-                return false;
+                false
             });
 
         for entry in walker {
@@ -143,7 +143,7 @@ impl MetadatadSite {
 
                 let typ = if let Some(extension) = entry.path().extension() {
                     let extension = extension.to_str().ok_or(Error::PathContainNonUnicode)?;
-                    DocumentType::try_from(extension).ok()
+                    DocumentType::from_extension(&site, extension)
                 } else {
                     None
                 };
@@ -16,14 +16,16 @@
 // You should have received a copy of the GNU General Public License
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
+mod dependency;
 mod full;
 mod metadatad;
 mod post;
 mod rendered;
 
 pub use self::{
+    dependency::DependencyGraph,
     full::{FullDocument, FullSite, FullWorkspace},
     metadatad::{MetadatadSite, MetadatadWorkspace, WorkspacePath},
-    post::{SimplePostSite, SimplePostWorkspace},
+    post::{SimplePostSite, SimplePostSource, SimplePostSourceKind, SimplePostWorkspace},
     rendered::{RenderedSite, RenderedWorkspace},
 };
@@ -17,8 +17,10 @@
 // along with Eighty. If not, see <http://www.gnu.org/licenses/>.
 
 use super::FullWorkspace;
-use crate::{Error, site::SiteName};
+use crate::{Error, feed, search, site::SiteName, sitemap_xml, specs::SpecRegistry};
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, ops::Deref, path::PathBuf};
+use tracing::error;
 
 pub struct SimplePostWorkspace(pub HashMap<SiteName, SimplePostSite>);
 
@@ -28,20 +30,79 @@ impl SimplePostWorkspace {
 
         for (site_name, full_site) in &full.sites {
             let mut post_site = HashMap::new();
+            let mut sources = HashMap::new();
 
             for (asset_path, asset) in &full_site.assets.assets {
                 post_site.insert(asset_path.clone(), asset.clone());
+                sources.insert(
+                    asset_path.clone(),
+                    SimplePostSource {
+                        kind: SimplePostSourceKind::Asset,
+                        path: full_site.assets.sources.get(asset_path).cloned(),
+                    },
+                );
             }
 
             for (file_path, file) in full_site.files.as_ref() {
                 post_site.insert(file_path.clone(), file.content.clone());
+                sources.insert(
+                    file_path.clone(),
+                    SimplePostSource {
+                        kind: SimplePostSourceKind::Passthrough,
+                        path: Some(
+                            file.source_path
+                                .strip_prefix(&full_site.site.source_path)?
+                                .to_owned(),
+                        ),
+                    },
+                );
+            }
+
+            for (page_path, page) in &full_site.pages {
+                if let Some(source) = sources.get(page_path) {
+                    error!(
+                        "[{}] Generated page {:?} conflicts with {:?} {:?}",
+                        site_name.0,
+                        page_path,
+                        source.kind,
+                        source.path.as_ref().unwrap_or(page_path)
+                    );
+                    return Err(Error::PageConflict);
+                }
+                post_site.insert(page_path.clone(), page.as_bytes().to_owned());
+                sources.insert(page_path.clone(), SimplePostSource::generated());
             }
 
             for document in &full_site.documents {
+                if full_site.pages.contains_key(&document.rendered.name.path()) {
+                    error!(
+                        "[{}] Document {:?} conflicts with the generated page {:?}",
+                        site_name.0,
+                        document.metadata.rel_source_path,
+                        document.rendered.name.path()
+                    );
+                    return Err(Error::PageConflict);
+                }
                 post_site.insert(
                     document.rendered.name.path(),
                     document.content.as_bytes().to_owned(),
                 );
+                sources.insert(
+                    document.rendered.name.path(),
+                    SimplePostSource {
+                        kind: SimplePostSourceKind::Document,
+                        path: Some(document.metadata.rel_source_path.clone()),
+                    },
+                );
+            }
+
+            let mut generated = HashMap::new();
+            generated.extend(feed::files(full_site)?);
+            generated.extend(sitemap_xml::files(full_site));
+            generated.extend(search::files(full_site)?);
+            for (file_path, file) in generated {
+                sources.insert(file_path.clone(), SimplePostSource::generated());
+                post_site.insert(file_path, file);
             }
 
             sites.insert(
@@ -49,6 +110,25 @@ impl SimplePostWorkspace {
                 SimplePostSite {
                     base_url: full_site.site.config.base_url.clone(),
                     files: post_site,
+                    sources,
+                },
+            );
+        }
+
+        let specs = SpecRegistry::new(full)?;
+        if !specs.0.is_empty() {
+            let files = specs.files()?;
+            let sources = files
+                .keys()
+                .map(|file_path| (file_path.clone(), SimplePostSource::generated()))
+                .collect();
+
+            sites.insert(
+                specs.site_name(),
+                SimplePostSite {
+                    base_url: "/".to_string(),
+                    files,
+                    sources,
                 },
             );
         }
@@ -68,4 +148,38 @@ impl Deref for SimplePostWorkspace {
 pub struct SimplePostSite {
     pub base_url: String,
     pub files: HashMap<PathBuf, Vec<u8>>,
+    /// Where each of `files` comes from.
+    pub sources: HashMap<PathBuf, SimplePostSource>,
+}
+
+#[derive(Eq, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimplePostSource {
+    pub kind: SimplePostSourceKind,
+    /// Source path relative to the site folder, if the file has one.
+    pub path: Option<PathBuf>,
+}
+
+impl SimplePostSource {
+    fn generated() -> Self {
+        Self {
+            kind: SimplePostSourceKind::Generated,
+            path: None,
+        }
+    }
+}
+
+#[derive(Eq, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SimplePostSourceKind {
+    /// A rendered document.
+    Document,
+    /// A layout asset or static file from `_assets`, or an asset bundled
+    /// with Eighty.
+    Asset,
+    /// A file copied as is from the site folder.
+    Passthrough,
+    /// A file generated by Eighty, such as feeds, sitemaps, the search
+    /// index and specifications.
+    Generated,
 }
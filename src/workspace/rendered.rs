@@ -18,10 +18,11 @@
 
 use crate::{
     Error,
+    cache::RenderCache,
     document::RenderedDocument,
     file::FileMetadata,
     site::{SiteMetadata, SiteName},
-    workspace::{MetadatadSite, MetadatadWorkspace, WorkspacePath},
+    workspace::{DependencyGraph, MetadatadSite, MetadatadWorkspace, WorkspacePath},
 };
 use rayon::prelude::*;
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
@@ -37,7 +38,7 @@ impl RenderedWorkspace {
         let sites = metadata
             .sites
             .par_iter()
-            .map(|(name, site)| Ok((name.clone(), RenderedSite::new(&site)?)))
+            .map(|(name, site)| Ok((name.clone(), RenderedSite::new(site)?)))
             .collect::<Result<_, Error>>()?;
 
         Ok(Self {
@@ -46,6 +47,23 @@ impl RenderedWorkspace {
         })
     }
 
+    pub fn new_with_cache(
+        metadata: &MetadatadWorkspace,
+        cache: &RenderCache,
+    ) -> Result<RenderedWorkspace, Error> {
+        let sites = metadata
+            .sites
+            .par_iter()
+            .map(|(name, site)| Ok((name.clone(), RenderedSite::new_with_cache(site, cache)?)))
+            .collect::<Result<_, Error>>()?;
+        cache.prune()?;
+
+        Ok(Self {
+            sites,
+            path: metadata.path.clone(),
+        })
+    }
+
     pub fn new_with_old(
         metadata: &MetadatadWorkspace,
         old: &RenderedWorkspace,
@@ -54,10 +72,10 @@ impl RenderedWorkspace {
             .sites
             .par_iter()
             .map(|(name, site)| {
-                if let Some(old_site) = old.sites.get(&name) {
-                    Ok((name.clone(), RenderedSite::new_with_old(&site, old_site)?))
+                if let Some(old_site) = old.sites.get(name) {
+                    Ok((name.clone(), RenderedSite::new_with_old(site, old_site)?))
                 } else {
-                    Ok((name.clone(), RenderedSite::new(&site)?))
+                    Ok((name.clone(), RenderedSite::new(site)?))
                 }
             })
             .collect::<Result<_, Error>>()?;
@@ -74,6 +92,7 @@ pub struct RenderedSite {
     pub site: Arc<SiteMetadata>,
     pub documents: HashMap<PathBuf, RenderedDocument>,
     pub files: Arc<HashMap<PathBuf, FileMetadata>>,
+    pub dependencies: DependencyGraph,
 }
 
 impl RenderedSite {
@@ -84,7 +103,8 @@ impl RenderedSite {
             .map(|document| {
                 Ok((
                     document.rel_source_path.clone(),
-                    RenderedDocument::new(metadata.site.clone(), document.clone())?,
+                    RenderedDocument::new(metadata.site.clone(), document.clone())
+                        .map_err(|err| err.in_document(&document.rel_source_path))?,
                 ))
             })
             .collect::<Result<_, Error>>()?;
@@ -92,6 +112,45 @@ impl RenderedSite {
         Ok(Self {
             site: metadata.site.clone(),
             files: metadata.files.clone(),
+            dependencies: DependencyGraph::new(&metadata.site, &documents),
+            documents,
+        })
+    }
+
+    pub fn new_with_cache(
+        metadata: &MetadatadSite,
+        cache: &RenderCache,
+    ) -> Result<RenderedSite, Error> {
+        let documents = metadata
+            .documents
+            .par_iter()
+            .map(|document| {
+                let key = cache
+                    .key(&metadata.site, document)
+                    .map_err(|err| err.in_document(&document.rel_source_path))?;
+                if let Some(data) = cache.get(&metadata.site, &key) {
+                    let rendered = RenderedDocument {
+                        site_metadata: metadata.site.clone(),
+                        metadata: document.clone(),
+                        data: Arc::new(data),
+                    };
+                    rendered.log_diagnostics();
+
+                    return Ok((document.rel_source_path.clone(), rendered));
+                }
+
+                let rendered = RenderedDocument::new(metadata.site.clone(), document.clone())
+                    .map_err(|err| err.in_document(&document.rel_source_path))?;
+                cache.insert(&metadata.site, &key, &rendered.data)?;
+
+                Ok((document.rel_source_path.clone(), rendered))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            site: metadata.site.clone(),
+            files: metadata.files.clone(),
+            dependencies: DependencyGraph::new(&metadata.site, &documents),
             documents,
         })
     }
@@ -100,21 +159,24 @@ impl RenderedSite {
         metadata: &MetadatadSite,
         old: &RenderedSite,
     ) -> Result<RenderedSite, Error> {
+        let invalidated = old.dependencies.invalidated(&metadata.site);
+
         let documents = metadata
             .documents
             .par_iter()
             .map(|document| {
-                if let Some(old_document) = old.documents.get(&document.rel_source_path) {
-                    if old_document.site_metadata == metadata.site
-                        && old_document.metadata == *document
-                    {
-                        return Ok((document.rel_source_path.clone(), old_document.clone()));
-                    }
+                if let Some(old_document) = old.documents.get(&document.rel_source_path)
+                    && old_document.site_metadata == metadata.site
+                    && old_document.metadata == *document
+                    && !invalidated.contains(&document.rel_source_path)
+                {
+                    return Ok((document.rel_source_path.clone(), old_document.clone()));
                 }
 
                 Ok((
                     document.rel_source_path.clone(),
-                    RenderedDocument::new(metadata.site.clone(), document.clone())?,
+                    RenderedDocument::new(metadata.site.clone(), document.clone())
+                        .map_err(|err| err.in_document(&document.rel_source_path))?,
                 ))
             })
             .collect::<Result<_, Error>>()?;
@@ -122,6 +184,7 @@ impl RenderedSite {
         Ok(Self {
             site: metadata.site.clone(),
             files: metadata.files.clone(),
+            dependencies: DependencyGraph::new(&metadata.site, &documents),
             documents,
         })
     }